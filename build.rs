@@ -4,5 +4,10 @@ fn main() {
             .manifest_required()
             .unwrap();
     }
-    slint_build::compile("ui/main.slint").unwrap();
+    // 界面翻译位于 translations/<语言>/LC_MESSAGES/hsarec.po
+    slint_build::compile_with_config(
+        "ui/main.slint",
+        slint_build::CompilerConfiguration::new().with_bundled_translations("translations"),
+    )
+    .unwrap();
 }
@@ -0,0 +1,416 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::path::PathBuf;
+
+use crate::paths::{self, AppPaths};
+
+#[cfg(windows)]
+mod win32;
+
+/// 开机启动时运行的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Command {
+    program: PathBuf,
+    args: Vec<String>,
+}
+
+impl Command {
+    /// 启动当前程序的命令, 带上 `--config` 以便开机后使用同一个配置文件
+    ///
+    /// 通过 `portable` 标记文件开启的便携模式由程序路径决定, 不需要额外的参数
+    fn new(exe: PathBuf, paths: &AppPaths) -> Result<Self> {
+        let mut args = Vec::new();
+        if paths.config_from_cli {
+            args.push("--config".to_string());
+            args.push(
+                std::path::absolute(&paths.config_file)?
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        Ok(Command { program: exe, args })
+    }
+}
+
+/// 让系统中的开机自动启动与配置一致
+///
+/// 已开启但启动项的命令与当前程序不同(例如程序被移动到了新目录, 或换了 `--config`)时重新写入
+pub fn sync(enabled: bool) -> Result<()> {
+    let command = Command::new(std::env::current_exe()?, paths::get())?;
+    match (enabled, registered_command()) {
+        (false, Ok(None)) => return Ok(()),
+        (true, Ok(Some(registered))) if registered == command => return Ok(()),
+        (true, Ok(Some(registered))) => info!(
+            "开机启动项 {:?} 与当前程序不一致, 将重新写入: {:?}",
+            registered, command
+        ),
+        (_, Err(e)) => warn!("无法读取开机启动项: {}", e),
+        _ => {}
+    }
+    set_enabled(enabled.then_some(&command))?;
+    if enabled {
+        info!("已开启开机自动启动。");
+    } else {
+        info!("已关闭开机自动启动。");
+    }
+    Ok(())
+}
+
+/// 生成 Windows 命令行, 程序路径总是加上引号
+#[cfg(any(windows, test))]
+fn command_line(command: &Command) -> String {
+    let program = format!("\"{}\"", command.program.display());
+    if command.args.is_empty() {
+        return program;
+    }
+    format!("{} {}", program, join_args(&command.args))
+}
+
+#[cfg(any(windows, test))]
+fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| crate::helper::quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 解析 `command_line` 生成的命令行, 程序路径可以不加引号
+#[cfg(any(windows, test))]
+fn parse_command_line(line: &str) -> Option<Command> {
+    let line = line.trim_start();
+    let (program, rest) = match line.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => line.split_once([' ', '\t']).unwrap_or((line, "")),
+    };
+    (!program.is_empty()).then(|| Command {
+        program: program.into(),
+        args: split_args(rest),
+    })
+}
+
+/// 按 `CommandLineToArgvW` 的规则拆分参数
+///
+/// 引号前的 2n 个反斜杠表示 n 个反斜杠且引号切换引用状态, 2n+1 个表示 n 个反斜杠和一个引号;
+/// 其他位置的反斜杠保持原样
+#[cfg(any(windows, test))]
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    let mut backslashes = 0;
+    for c in line.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let pending = std::mem::take(&mut backslashes);
+        match c {
+            '"' => {
+                let arg = current.get_or_insert_default();
+                arg.extend(std::iter::repeat_n('\\', pending / 2));
+                if pending % 2 == 1 {
+                    arg.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+            }
+            ' ' | '\t' if !quoted => {
+                if pending > 0 {
+                    current
+                        .get_or_insert_default()
+                        .extend(std::iter::repeat_n('\\', pending));
+                }
+                args.extend(current.take());
+            }
+            c => {
+                let arg = current.get_or_insert_default();
+                arg.extend(std::iter::repeat_n('\\', pending));
+                arg.push(c);
+            }
+        }
+    }
+    if backslashes > 0 {
+        current
+            .get_or_insert_default()
+            .extend(std::iter::repeat_n('\\', backslashes));
+    }
+    args.extend(current);
+    args
+}
+
+#[cfg(windows)]
+fn registered_command() -> Result<Option<Command>> {
+    win32::registered()
+}
+
+#[cfg(windows)]
+fn set_enabled(command: Option<&Command>) -> Result<()> {
+    win32::set_enabled(command)
+}
+
+/// 按 desktop entry 规范生成 `Exec` 键的值
+///
+/// 每个参数都加上引号, 其中的 `"` `` ` `` `$` `\` 前加反斜杠, `%` 写成 `%%`;
+/// 之后再按字符串类型的规则转义反斜杠和换行
+#[cfg(any(target_os = "linux", test))]
+fn exec_line(command: &Command) -> String {
+    std::iter::once(command.program.to_string_lossy().into_owned())
+        .chain(command.args.iter().cloned())
+        .map(|arg| {
+            let mut quoted = String::from('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted.replace('%', "%%")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('\\', r"\\")
+        .replace('\n', r"\n")
+}
+
+/// 解析 `Exec` 键的值, 引号不成对时为 `None`
+#[cfg(any(target_os = "linux", test))]
+fn parse_exec_line(value: &str) -> Option<Command> {
+    // 先还原字符串类型的转义
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(match c {
+            '\\' => match chars.next()? {
+                's' => ' ',
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                c => c,
+            },
+            c => c,
+        });
+    }
+
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = unescaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => args.extend(current.take()),
+            '"' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => arg.push(chars.next()?),
+                        c => arg.push(c),
+                    }
+                }
+            }
+            c => current.get_or_insert_default().push(c),
+        }
+    }
+    args.extend(current);
+    let mut args = args.into_iter().map(|arg| arg.replace("%%", "%"));
+    Some(Command {
+        program: args.next()?.into(),
+        args: args.collect(),
+    })
+}
+
+/// XDG autostart 目录中的启动项
+#[cfg(target_os = "linux")]
+fn desktop_file() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("无法获取用户配置目录"))?;
+    Ok(config_dir.join("autostart").join("hsarec.desktop"))
+}
+
+/// 启动项文件中的命令, 文件不存在时为 `None`
+#[cfg(target_os = "linux")]
+fn registered_command() -> Result<Option<Command>> {
+    let path = desktop_file()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let exec = content
+        .lines()
+        .find_map(|line| line.strip_prefix("Exec="))
+        .ok_or_else(|| anyhow::anyhow!("启动项 {:?} 中没有 Exec 键", path))?;
+    parse_exec_line(exec)
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("无法解析启动项中的命令: {}", exec))
+}
+
+#[cfg(target_os = "linux")]
+fn set_enabled(command: Option<&Command>) -> Result<()> {
+    let path = desktop_file()?;
+    let Some(command) = command else {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(
+        &path,
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Hsarec\nExec={}\nX-GNOME-Autostart-enabled=true\n",
+            exec_line(command)
+        ),
+    )?;
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn registered_command() -> Result<Option<Command>> {
+    Ok(None)
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn set_enabled(_command: Option<&Command>) -> Result<()> {
+    anyhow::bail!("当前平台不支持开机自动启动")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(program: &str, args: &[&str]) -> Command {
+        Command {
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn app_paths(config_file: &str, config_from_cli: bool) -> AppPaths {
+        AppPaths {
+            config_file: config_file.into(),
+            log_file: PathBuf::new(),
+            portable: true,
+            config_from_cli,
+        }
+    }
+
+    #[test]
+    fn carries_over_config_argument() {
+        let paths = app_paths("/opt/hsarec/config.toml", false);
+        let plain = Command::new("/opt/hsarec/hsarec".into(), &paths).unwrap();
+        assert_eq!(plain, command("/opt/hsarec/hsarec", &[]));
+
+        let paths = app_paths("my config.toml", true);
+        let with_config = Command::new("/opt/hsarec/hsarec".into(), &paths).unwrap();
+        let absolute = std::env::current_dir().unwrap().join("my config.toml");
+        assert_eq!(with_config.args, ["--config", absolute.to_str().unwrap()]);
+        assert_ne!(plain, with_config);
+    }
+
+    #[test]
+    fn builds_windows_command_line() {
+        assert_eq!(
+            command_line(&command(r"C:\Program Files\hsarec\hsarec.exe", &[])),
+            r#""C:\Program Files\hsarec\hsarec.exe""#
+        );
+        assert_eq!(
+            command_line(&command(
+                r"C:\hsarec\hsarec.exe",
+                &["--config", r"D:\my dir\config.toml"]
+            )),
+            r#""C:\hsarec\hsarec.exe" --config "D:\my dir\config.toml""#
+        );
+    }
+
+    #[test]
+    fn parses_windows_command_line() {
+        assert_eq!(
+            parse_command_line(r#""C:\Program Files\hsarec\hsarec.exe""#),
+            Some(command(r"C:\Program Files\hsarec\hsarec.exe", &[]))
+        );
+        assert_eq!(
+            parse_command_line(r"C:\hsarec\hsarec.exe --config C:\hsarec\config.toml"),
+            Some(command(
+                r"C:\hsarec\hsarec.exe",
+                &["--config", r"C:\hsarec\config.toml"]
+            ))
+        );
+        assert_eq!(parse_command_line(r#""C:\hsarec\hsarec.exe"#), None);
+        assert_eq!(parse_command_line(""), None);
+        // 只比较完整路径, 不再按子串匹配
+        assert_ne!(
+            parse_command_line(r#""C:\hsarec\hsarec.exe.old""#),
+            Some(command(r"C:\hsarec\hsarec.exe", &[]))
+        );
+    }
+
+    #[test]
+    fn splits_args_like_command_line_to_argv() {
+        assert_eq!(split_args(""), Vec::<String>::new());
+        assert_eq!(split_args(r#"a  "b c" """#), ["a", "b c", ""]);
+        assert_eq!(
+            split_args(r#"a\\\"b "c\\" d\e\"#),
+            [r#"a\"b"#, r"c\", r"d\e\"]
+        );
+        assert_eq!(split_args(r"a\ b"), [r"a\", "b"]);
+    }
+
+    #[test]
+    fn windows_command_line_round_trips() {
+        for command in [
+            command(r"C:\hsarec\hsarec.exe", &[]),
+            command(
+                r"C:\Program Files\hsarec\hsarec.exe",
+                &["--config", r"D:\my dir\", r#"say "hi""#, "", r"a\\b c"],
+            ),
+        ] {
+            assert_eq!(parse_command_line(&command_line(&command)), Some(command));
+        }
+    }
+
+    #[test]
+    fn builds_desktop_exec_line() {
+        assert_eq!(
+            exec_line(&command("/opt/hsarec/hsarec", &[])),
+            r#""/opt/hsarec/hsarec""#
+        );
+        assert_eq!(
+            exec_line(&command(
+                "/opt/hs arec/hsarec",
+                &["--config", r#"/home/a/$x`"\100%.toml"#]
+            )),
+            r#""/opt/hs arec/hsarec" "--config" "/home/a/\\$x\\`\\"\\\\100%%.toml""#
+        );
+    }
+
+    #[test]
+    fn parses_desktop_exec_line() {
+        assert_eq!(
+            parse_exec_line(r#""/opt/hsarec/hsarec""#),
+            Some(command("/opt/hsarec/hsarec", &[]))
+        );
+        assert_eq!(
+            parse_exec_line("/opt/hsarec/hsarec --config /tmp/config.toml"),
+            Some(command(
+                "/opt/hsarec/hsarec",
+                &["--config", "/tmp/config.toml"]
+            ))
+        );
+        assert_eq!(parse_exec_line(r#""/opt/hsarec/hsarec"#), None);
+        assert_eq!(parse_exec_line(""), None);
+    }
+
+    #[test]
+    fn desktop_exec_line_round_trips() {
+        for command in [
+            command("/opt/hsarec/hsarec", &[]),
+            command(
+                "/opt/hs arec/hsarec",
+                &["--config", r#"/a/$b`c"d\e%f%%g"#, "", "line\nbreak"],
+            ),
+        ] {
+            assert_eq!(parse_exec_line(&exec_line(&command)), Some(command));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use log::warn;
+use std::os::windows::ffi::OsStrExt;
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, RPC_E_CHANGED_MODE, VARIANT_FALSE};
+use windows::Win32::System::Com::{
+    CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx,
+    CoUninitialize,
+};
+use windows::Win32::System::Registry::{
+    HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ, RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW,
+};
+use windows::Win32::System::TaskScheduler::{
+    IExecAction, ILogonTrigger, ITaskFolder, ITaskService, TASK_ACTION_EXEC, TASK_CREATE_OR_UPDATE,
+    TASK_LOGON_INTERACTIVE_TOKEN, TASK_RUNLEVEL_HIGHEST, TASK_TRIGGER_LOGON, TaskScheduler,
+};
+use windows::Win32::System::Variant::VARIANT;
+use windows::core::{BSTR, HSTRING, Interface};
+
+use super::Command;
+
+/// 计划任务名称和 Run 键值名称
+const NAME: &str = "Hsarec";
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// 已写入的启动项, 计划任务和 Run 键都不存在时为 `None`
+pub fn registered() -> Result<Option<Command>> {
+    match registered_task()? {
+        Some(command) => Ok(Some(command)),
+        None => registered_run_key(),
+    }
+}
+
+/// 以管理员权限运行时使用登录时以最高权限运行的计划任务,
+/// 因为 Run 键无法启动需要管理员权限的程序; 否则写入当前用户的 Run 键
+///
+/// 只保留一种启动项, 以免旧的启动项指向其他位置或登录时启动两次
+pub fn set_enabled(command: Option<&Command>) -> Result<()> {
+    match command {
+        None => {
+            delete_task()?;
+            delete_run_key()
+        }
+        Some(command) if crate::helper::is_elevated() => {
+            register_task(command)?;
+            delete_run_key()
+        }
+        Some(command) => {
+            set_run_key(command)?;
+            // 没有管理员权限时可能无法删除以最高权限运行的计划任务
+            if let Err(e) = delete_task() {
+                warn!("无法删除开机启动的计划任务: {}", e);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn registered_run_key() -> Result<Option<Command>> {
+    let (key, name) = (HSTRING::from(RUN_KEY), HSTRING::from(NAME));
+    let mut size = 0;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            &name,
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        )
+    };
+    if result == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    result.to_hresult().ok()?;
+    let mut value = vec![0u16; size as usize / 2];
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            &name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(value.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    }
+    .to_hresult()
+    .ok()?;
+    let line = String::from_utf16_lossy(&value[..size as usize / 2]);
+    let line = line.trim_end_matches('\0');
+    super::parse_command_line(line)
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("无法解析 Run 键中的命令: {}", line))
+}
+
+fn set_run_key(command: &Command) -> Result<()> {
+    let value: Vec<u16> = super::command_line(command)
+        .encode_utf16()
+        .chain([0])
+        .collect();
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(RUN_KEY),
+            &HSTRING::from(NAME),
+            REG_SZ.0,
+            Some(value.as_ptr().cast()),
+            (value.len() * 2) as u32,
+        )
+    }
+    .to_hresult()
+    .ok()?;
+    Ok(())
+}
+
+fn delete_run_key() -> Result<()> {
+    let result = unsafe {
+        RegDeleteKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(RUN_KEY),
+            &HSTRING::from(NAME),
+        )
+    };
+    if result != ERROR_FILE_NOT_FOUND {
+        result.to_hresult().ok()?;
+    }
+    Ok(())
+}
+
+/// 在当前线程初始化 COM, 当前线程已按其他模式初始化时直接使用
+///
+/// 需要在创建任何 COM 对象之前创建, 以便最后释放
+struct ComGuard {
+    initialized: bool,
+}
+
+impl ComGuard {
+    fn new() -> Result<Self> {
+        let result = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+        if result == RPC_E_CHANGED_MODE {
+            return Ok(ComGuard { initialized: false });
+        }
+        result.ok()?;
+        Ok(ComGuard { initialized: true })
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+fn connect() -> Result<ITaskService> {
+    let service: ITaskService =
+        unsafe { CoCreateInstance(&TaskScheduler, None, CLSCTX_INPROC_SERVER) }?;
+    let empty = VARIANT::default();
+    unsafe { service.Connect(&empty, &empty, &empty, &empty) }?;
+    Ok(service)
+}
+
+fn root_folder(service: &ITaskService) -> Result<ITaskFolder> {
+    Ok(unsafe { service.GetFolder(&BSTR::from(r"\")) }?)
+}
+
+fn registered_task() -> Result<Option<Command>> {
+    let _com = ComGuard::new()?;
+    let folder = root_folder(&connect()?)?;
+    let task = match unsafe { folder.GetTask(&BSTR::from(NAME)) } {
+        Ok(task) => task,
+        Err(e) if e.code() == ERROR_FILE_NOT_FOUND.to_hresult() => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let action: IExecAction = unsafe { task.Definition()?.Actions()?.get_Item(1)? }.cast()?;
+    let (mut path, mut args) = (BSTR::new(), BSTR::new());
+    unsafe {
+        action.Path(&mut path)?;
+        action.Arguments(&mut args)?;
+    }
+    Ok(Some(Command {
+        program: path.to_string().into(),
+        args: super::split_args(&args.to_string()),
+    }))
+}
+
+fn register_task(command: &Command) -> Result<()> {
+    let _com = ComGuard::new()?;
+    let service = connect()?;
+    unsafe {
+        let user = BSTR::from(format!(
+            r"{}\{}",
+            service.ConnectedDomain()?,
+            service.ConnectedUser()?
+        ));
+        let task = service.NewTask(0)?;
+        // 只在当前用户登录时启动
+        let trigger: ILogonTrigger = task.Triggers()?.Create(TASK_TRIGGER_LOGON)?.cast()?;
+        trigger.SetUserId(&user)?;
+        let principal = task.Principal()?;
+        principal.SetUserId(&user)?;
+        principal.SetLogonType(TASK_LOGON_INTERACTIVE_TOKEN)?;
+        principal.SetRunLevel(TASK_RUNLEVEL_HIGHEST)?;
+        // 不限制运行时间, 使用电池时也启动
+        let settings = task.Settings()?;
+        settings.SetExecutionTimeLimit(&BSTR::from("PT0S"))?;
+        settings.SetDisallowStartIfOnBatteries(VARIANT_FALSE)?;
+        settings.SetStopIfGoingOnBatteries(VARIANT_FALSE)?;
+        let action: IExecAction = task.Actions()?.Create(TASK_ACTION_EXEC)?.cast()?;
+        let program: Vec<u16> = command.program.as_os_str().encode_wide().collect();
+        action.SetPath(&BSTR::from_wide(&program))?;
+        action.SetArguments(&BSTR::from(super::join_args(&command.args)))?;
+        let empty = VARIANT::default();
+        root_folder(&service)?.RegisterTaskDefinition(
+            &BSTR::from(NAME),
+            &task,
+            TASK_CREATE_OR_UPDATE.0,
+            &empty,
+            &empty,
+            TASK_LOGON_INTERACTIVE_TOKEN,
+            &empty,
+        )?;
+    }
+    Ok(())
+}
+
+fn delete_task() -> Result<()> {
+    let _com = ComGuard::new()?;
+    let folder = root_folder(&connect()?)?;
+    match unsafe { folder.DeleteTask(&BSTR::from(NAME), 0) } {
+        Err(e) if e.code() != ERROR_FILE_NOT_FOUND.to_hresult() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,66 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+#[cfg(windows)]
+use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+
+/// 炉石传说拔线工具
+#[derive(Parser, Debug)]
+#[command(name = "hsarec", version)]
+pub struct Cli {
+    /// 使用指定的配置文件, 日志写到该文件所在目录
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+    /// 覆盖配置项, 可重复使用, 例如 `--set focus_guard=true --set degrade.delay_ms=500`
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub set: Vec<String>,
+    /// 切换到指定的配置档并保存
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// 启动后立即拔线; 已有实例在运行时交给该实例执行
+    #[arg(long)]
+    pub disconnect: bool,
+    /// 启动后对游戏服务器进行一次弱网模拟; 已有实例在运行时交给该实例执行
+    #[arg(long)]
+    pub degrade: bool,
+    /// 启动后打开设置窗口; 已有实例在运行时交给该实例执行
+    #[arg(long)]
+    pub show_settings: bool,
+    /// 等待旧实例退出而不是把命令转发给它, 以管理员权限重新启动时使用
+    #[arg(long, hide = true)]
+    pub wait_for_lock: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 配置相关命令
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// 以管理员权限运行的辅助进程, 由界面进程启动, 代为关闭炉石的连接
+    #[command(hide = true)]
+    Helper {
+        /// 界面进程创建的命名管道
+        #[arg(long, value_name = "NAME")]
+        pipe: String,
+        /// 连接管道后发送的认证令牌
+        #[arg(long)]
+        token: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// 显示生效的配置及每一项的来源
+    Show,
+}
+
+/// 以 GUI 子系统编译时没有控制台, 命令行子命令需要附加到父进程的控制台才能输出
+pub fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
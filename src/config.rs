@@ -1,23 +1,355 @@
 use anyhow::Result;
-use log::{info, warn};
+use flume::{Sender, unbounded};
+use log::{error, info, warn};
+use notify::{Event, EventKind, PollWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, OnceLock, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::hotkey::{HotkeyAction, HotkeySequence};
+use crate::overrides::{self, Overrides};
+use crate::paths;
+
+/// 当前配置文件格式版本
+pub const CONFIG_VERSION: u32 = 3;
+
+/// 配置迁移, 第 i 个函数把版本 i+1 的配置升级到版本 i+2
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize - 1] =
+    [migrate_v1_to_v2, migrate_v2_to_v3];
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
-    pub reconnect_hotkey: String,
+    /// 配置文件格式版本, 没有该字段的旧配置视为版本 1
+    pub version: u32,
+    /// 热键组合到动作的映射
+    #[serde(default = "default_hotkeys")]
+    pub hotkeys: BTreeMap<String, HotkeyAction>,
+    /// 严格匹配: 热键未包含的修饰键必须处于松开状态
+    #[serde(default = "default_strict_hotkeys")]
+    pub strict_hotkeys: bool,
+    /// 按键序列(如 `Ctrl+K, D`)相邻两步之间允许的最长间隔(毫秒)
+    #[serde(default = "default_chord_timeout_ms")]
+    pub chord_timeout_ms: u64,
+    /// 焦点保护: 只有炉石窗口在前台时才响应热键
+    #[serde(default)]
+    pub focus_guard: bool,
+    /// 当前使用的配置档名称
+    #[serde(default = "default_profile_name")]
+    pub profile: String,
+    /// 配置档名称到拔线设置的映射
+    #[serde(default = "default_profiles")]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    pub degrade: DegradeConfig,
+    /// 日志文件路径, 为空时使用默认位置, 重启后生效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    /// 开机自动启动
+    #[serde(default)]
+    pub auto_start: bool,
+    /// 炉石退出时本程序随之退出
+    #[serde(default)]
+    pub exit_with_game: bool,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// 界面语言
+    #[serde(default)]
+    pub language: Language,
+    /// 无法识别的配置项, 原样保留以免保存时丢失(例如由更新版本写入的配置)
+    #[serde(flatten)]
+    pub extra: toml::Table,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            reconnect_hotkey: "Shift+Alt+R".to_string(),
+            version: CONFIG_VERSION,
+            hotkeys: default_hotkeys(),
+            strict_hotkeys: default_strict_hotkeys(),
+            chord_timeout_ms: default_chord_timeout_ms(),
+            focus_guard: false,
+            profile: default_profile_name(),
+            profiles: default_profiles(),
+            degrade: DegradeConfig::default(),
+            log_file: None,
+            auto_start: false,
+            exit_with_game: false,
+            notifications: NotificationsConfig::default(),
+            language: Language::default(),
+            extra: toml::Table::new(),
+        }
+    }
+}
+
+/// 版本 1 只有单一的 `reconnect_hotkey`, 版本 2 改为 `[hotkeys]` 表
+fn migrate_v1_to_v2(table: &mut toml::Table) {
+    let Some(toml::Value::String(legacy)) = table.remove("reconnect_hotkey") else {
+        return;
+    };
+    info!("已将旧配置 reconnect_hotkey = {:?} 并入 [hotkeys]", legacy);
+    let hotkeys = table
+        .entry("hotkeys")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(hotkeys) = hotkeys {
+        hotkeys.retain(|_, action| action.as_str() != Some("disconnect"));
+        hotkeys.insert(legacy, toml::Value::String("disconnect".to_string()));
+    }
+}
+
+/// 版本 3 去掉了没有实际作用的自动模式及其热键
+fn migrate_v2_to_v3(table: &mut toml::Table) {
+    if table.remove("auto_mode").is_some() {
+        info!("已删除不再使用的配置 auto_mode");
+    }
+    if let Some(toml::Value::Table(hotkeys)) = table.get_mut("hotkeys") {
+        hotkeys.retain(|hotkey, action| {
+            let removed = action.as_str() == Some("toggle-auto-mode");
+            if removed {
+                info!("已删除切换自动模式的热键 {}", hotkey);
+            }
+            !removed
+        });
+    }
+}
+
+/// 把配置升级到当前版本, 返回文件原本的版本
+fn migrate(table: &mut toml::Table) -> Result<u32> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) if *version >= 1 => u32::try_from(*version)?,
+        Some(value) => return Err(anyhow::anyhow!("无效的配置版本: {}", value)),
+    };
+    // 更新版本的配置可能改变了已有配置项的含义, 不按当前版本解析, 也不覆盖
+    anyhow::ensure!(
+        version <= CONFIG_VERSION,
+        "配置文件版本 {} 高于当前支持的版本 {}, 请升级本程序",
+        version,
+        CONFIG_VERSION
+    );
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(table);
+    }
+    table.insert(
+        "version".to_string(),
+        toml::Value::Integer(CONFIG_VERSION.into()),
+    );
+    Ok(version)
+}
+
+fn default_hotkeys() -> BTreeMap<String, HotkeyAction> {
+    BTreeMap::from([("Shift+Alt+R".to_string(), HotkeyAction::Disconnect)])
+}
+
+fn default_strict_hotkeys() -> bool {
+    true
+}
+
+fn default_chord_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_profile_name() -> String {
+    "default".to_string()
+}
+
+fn default_profiles() -> BTreeMap<String, Profile> {
+    BTreeMap::from([
+        ("default".to_string(), Profile::default()),
+        (
+            "ladder".to_string(),
+            Profile {
+                strategy: DisconnectStrategy::Server,
+                cooldown_secs: 30,
+                max_per_game: 10,
+            },
+        ),
+        (
+            "testing".to_string(),
+            Profile {
+                strategy: DisconnectStrategy::All,
+                cooldown_secs: 0,
+                max_per_game: 0,
+            },
+        ),
+    ])
+}
+
+/// 拔线方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisconnectStrategy {
+    /// 只断开日志中记录的游戏服务器连接
+    Server,
+    /// 断开炉石的全部连接
+    All,
+}
+
+/// 配置档: 一组拔线相关的设置, 可以在托盘菜单或命令行中切换
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub strategy: DisconnectStrategy,
+    /// 两次拔线之间的最短间隔(秒), 0 表示不限制
+    pub cooldown_secs: u64,
+    /// 每局最多拔线次数, 0 表示不限制
+    pub max_per_game: u32,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            strategy: DisconnectStrategy::Server,
+            cooldown_secs: 0,
+            max_per_game: 0,
+        }
+    }
+}
+
+/// 弱网模拟配置: 对日志中的游戏服务器注入延迟/丢包而不是直接断开
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DegradeConfig {
+    /// 附加延迟(毫秒)
+    pub delay_ms: u32,
+    /// 丢包率(百分比)
+    pub loss_percent: f32,
+    /// 持续时间(秒), 到期后自动恢复
+    pub duration_secs: u64,
+    /// 指定网卡(仅 Linux netem 使用), 为空时按路由自动选择
+    pub interface: Option<String>,
+}
+
+impl Default for DegradeConfig {
+    fn default() -> Self {
+        DegradeConfig {
+            delay_ms: 800,
+            loss_percent: 10.0,
+            duration_secs: 15,
+            interface: None,
+        }
+    }
+}
+
+/// 桌面通知设置
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// 是否显示通知, 关闭后以下各项均不生效
+    pub enabled: bool,
+    /// 开始拔线
+    pub disconnect_started: bool,
+    /// 游戏重新连接
+    pub reconnected: bool,
+    /// 拔线失败
+    pub failed: bool,
+    /// 本局拔线次数达到上限
+    pub cap_reached: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig {
+            enabled: true,
+            disconnect_started: true,
+            reconnected: true,
+            failed: true,
+            cap_reached: true,
         }
     }
 }
 
+/// 界面语言
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    #[serde(rename = "en")]
+    En,
+}
+
+impl Language {
+    /// 对应的界面翻译名称, 简体中文为界面原文
+    pub fn locale(self) -> &'static str {
+        match self {
+            Language::ZhCn => "",
+            Language::En => "en",
+        }
+    }
+}
+
+/// 一条配置校验错误: 出错的配置项、当前值以及原因
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    pub value: String,
+    pub reason: String,
+}
+
+impl ValidationError {
+    fn new(field: impl Into<String>, value: impl ToString, reason: impl Into<String>) -> Self {
+        ValidationError {
+            field: field.into(),
+            value: value.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.value.is_empty() {
+            write!(f, "{}: {}", self.field, self.reason)
+        } else {
+            write!(f, "{} = {}: {}", self.field, self.value, self.reason)
+        }
+    }
+}
+
+/// 配置文件中的全部错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigErrors(pub Vec<ValidationError>);
+
+impl ConfigErrors {
+    /// 把 TOML 解析错误转换为配置错误, 能定位时附带行号
+    fn from_toml(contents: &str, error: &toml::de::Error) -> Self {
+        let reason = match error.span() {
+            Some(span) => format!(
+                "第 {} 行: {}",
+                contents.get(..span.start).unwrap_or(contents).matches('\n').count() + 1,
+                error.message()
+            ),
+            None => error.message().to_string(),
+        };
+        ConfigErrors(vec![ValidationError::new("config.toml", "", reason)])
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// 磁盘上的配置文件当前是否无效, 无效时拒绝保存以免覆盖用户的修改
+static FILE_INVALID: AtomicBool = AtomicBool::new(false);
+
+/// 启动加载配置时发现的错误, 等界面就绪后再展示给用户
+static LOAD_ERRORS: Mutex<Option<ConfigErrors>> = Mutex::new(None);
+
+/// 取出启动时加载配置发现的错误
+pub fn take_load_errors() -> Option<ConfigErrors> {
+    LOAD_ERRORS.lock().ok().and_then(|mut errors| errors.take())
+}
+
 impl Config {
     /// 从文件加载配置
     pub fn load() -> Self {
@@ -25,7 +357,7 @@ impl Config {
             Ok(path) => path,
             Err(msg) => {
                 warn!("警告: {}，将使用默认配置。", msg);
-                return Self::default();
+                return Self::default().with_overrides();
             }
         };
 
@@ -33,7 +365,7 @@ impl Config {
             Self::load_from_file(&config_path)
         } else {
             warn!("警告: 配置文件 {:?} 不存在。将使用默认配置。", config_path);
-            let default_config = Self::default();
+            let default_config = Self::default().with_overrides();
             if let Err(e) = default_config.save_to_file(&config_path) {
                 warn!("警告: 无法创建默认配置文件: {}", e);
             } else {
@@ -45,56 +377,415 @@ impl Config {
         config
     }
 
-    /// 保存配置到文件
+    pub fn chord_timeout(&self) -> Duration {
+        Duration::from_millis(self.chord_timeout_ms)
+    }
+
+    /// 当前配置档, 不存在时使用默认设置
+    pub fn active_profile(&self) -> Profile {
+        self.profiles
+            .get(&self.profile)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// 切换到名为 `name` 的配置档
+    pub fn select_profile(&mut self, name: &str) -> Result<()> {
+        anyhow::ensure!(
+            self.profiles.contains_key(name),
+            "配置档 '{}' 不存在, 可用的配置档: {:?}",
+            name,
+            self.profiles.keys().collect::<Vec<_>>()
+        );
+        self.profile = name.to_string();
+        Ok(())
+    }
+
+    /// 获取绑定到指定动作的第一个热键
+    pub fn hotkey_for(&self, action: HotkeyAction) -> Option<&str> {
+        self.hotkeys
+            .iter()
+            .find(|(_, bound)| **bound == action)
+            .map(|(combo, _)| combo.as_str())
+    }
+
+    /// 将 `action` 重新绑定到 `combo`, 移除该动作原有的热键
+    pub fn set_hotkey(&mut self, action: HotkeyAction, combo: String) {
+        self.hotkeys.retain(|_, bound| *bound != action);
+        self.hotkeys.insert(combo, action);
+    }
+
+    /// 检查配置内容是否可用, 返回全部出错的配置项
+    pub fn validate(&self) -> std::result::Result<(), ConfigErrors> {
+        let mut errors = Vec::new();
+        for combo in self.hotkeys.keys() {
+            if let Err(e) = combo.parse::<HotkeySequence>() {
+                errors.push(ValidationError::new(
+                    format!("hotkeys.\"{}\"", combo),
+                    "",
+                    format!("热键无效: {}", e),
+                ));
+            }
+        }
+        if !(1..=10_000).contains(&self.chord_timeout_ms) {
+            errors.push(ValidationError::new(
+                "chord_timeout_ms",
+                self.chord_timeout_ms,
+                "必须在 1 到 10000 之间",
+            ));
+        }
+        if !self.profiles.contains_key(&self.profile) {
+            errors.push(ValidationError::new(
+                "profile",
+                &self.profile,
+                format!(
+                    "配置档不存在, 可用的配置档: {:?}",
+                    self.profiles.keys().collect::<Vec<_>>()
+                ),
+            ));
+        }
+        if !(0.0..=100.0).contains(&self.degrade.loss_percent) {
+            errors.push(ValidationError::new(
+                "degrade.loss_percent",
+                self.degrade.loss_percent,
+                "必须在 0 到 100 之间",
+            ));
+        }
+        if self.degrade.duration_secs == 0 {
+            errors.push(ValidationError::new(
+                "degrade.duration_secs",
+                self.degrade.duration_secs,
+                "必须大于 0",
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(errors))
+        }
+    }
+
+    /// 列出 `other` 相对于当前配置发生变化的配置项, 例如 `degrade.delay_ms: 800 -> 500`
+    pub fn diff(&self, other: &Config) -> Vec<String> {
+        let (Ok(toml::Value::Table(old)), Ok(toml::Value::Table(new))) =
+            (toml::Value::try_from(self), toml::Value::try_from(other))
+        else {
+            return vec!["无法比较配置".to_string()];
+        };
+        let mut changes = Vec::new();
+        diff_tables("", &old, &new, &mut changes);
+        changes
+    }
+
+    /// 保存配置到文件, 磁盘上的配置文件无效时拒绝覆盖
     pub fn save(&self) -> Result<()> {
         let config_path = Self::get_config_path()?;
+        anyhow::ensure!(
+            !FILE_INVALID.load(Ordering::SeqCst),
+            "配置文件 {:?} 存在错误, 修复之前不会覆盖该文件。",
+            config_path
+        );
         self.save_to_file(&config_path)
     }
 
     /// 获取配置文件路径
     fn get_config_path() -> Result<PathBuf> {
-        let current_exe = std::env::current_exe()?;
-        let parent_dir = current_exe
-            .parent()
-            .ok_or_else(|| anyhow::anyhow!("无法获取当前可执行文件的父目录"))?;
-        Ok(parent_dir.join("config.toml"))
+        Ok(paths::get().config_file.clone())
     }
 
     /// 从指定文件加载配置
-    fn load_from_file(config_path: &PathBuf) -> Self {
-        match fs::read_to_string(config_path) {
-            Ok(contents) => match toml::from_str(&contents) {
-                Ok(config) => {
-                    info!("成功加载配置文件: {:?}", config_path);
-                    config
-                }
-                Err(e) => {
-                    warn!(
-                        "警告: 解析配置文件 {:?} 失败: {}。将使用默认配置。",
-                        config_path, e
-                    );
-                    Self::default()
-                }
-            },
+    ///
+    /// 文件无法解析时先备份再使用默认配置, 避免下次保存时覆盖用户的配置
+    fn load_from_file(config_path: &Path) -> Self {
+        let contents = match fs::read_to_string(config_path) {
+            Ok(contents) => contents,
             Err(e) => {
                 warn!(
                     "警告: 读取配置文件 {:?} 失败: {}。将使用默认配置。",
                     config_path, e
                 );
-                Self::default()
+                return Self::default().with_overrides();
+            }
+        };
+
+        match Self::parse(&contents) {
+            Ok((config, from_version)) => {
+                info!("成功加载配置文件: {:?}", config_path);
+                if !config.extra.is_empty() {
+                    let keys: Vec<&String> = config.extra.keys().collect();
+                    warn!("警告: 无法识别的配置项 {:?} 将原样保留。", keys);
+                }
+                if from_version < CONFIG_VERSION {
+                    info!(
+                        "配置文件已从版本 {} 升级到版本 {}。",
+                        from_version, CONFIG_VERSION
+                    );
+                    match Self::backup(config_path, &format!("v{}", from_version)) {
+                        Ok(_) => {
+                            if let Err(e) = config.save_to_file(config_path) {
+                                warn!("警告: 无法保存升级后的配置文件: {}", e);
+                            }
+                        }
+                        Err(e) => warn!("警告: 无法备份旧版本配置文件, 暂不写回: {}", e),
+                    }
+                }
+                config
+            }
+            Err(errors) => {
+                for error in &errors.0 {
+                    warn!("警告: 配置文件 {:?} 有误: {}", config_path, error);
+                }
+                warn!("警告: 将使用默认配置, 修复之前不会覆盖该文件。");
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                if let Err(e) = Self::backup(config_path, &format!("broken-{}", now)) {
+                    warn!("警告: 无法备份有误的配置文件: {}", e);
+                }
+                FILE_INVALID.store(true, Ordering::SeqCst);
+                if let Ok(mut load_errors) = LOAD_ERRORS.lock() {
+                    *load_errors = Some(errors);
+                }
+                Self::default().with_overrides()
             }
         }
     }
 
+    /// 解析并校验配置内容, 升级到当前版本后叠加覆盖项, 同时返回原本的版本
+    fn parse(contents: &str) -> std::result::Result<(Self, u32), ConfigErrors> {
+        let mut table = toml::from_str::<toml::Table>(contents)
+            .map_err(|e| ConfigErrors::from_toml(contents, &e))?;
+        let from_version = migrate(&mut table).map_err(|e| {
+            ConfigErrors(vec![ValidationError::new(
+                "version",
+                table.get("version").map(|v| v.to_string()).unwrap_or_default(),
+                e.to_string(),
+            )])
+        })?;
+        let config = Self::from_table(table).map_err(|e| {
+            // 未经升级的文件直接按原文解析一次, 以便定位出错的行
+            match toml::from_str::<Self>(contents) {
+                Err(spanned) if from_version == CONFIG_VERSION => {
+                    ConfigErrors::from_toml(contents, &spanned)
+                }
+                _ => ConfigErrors::from_toml(contents, &e),
+            }
+        })?;
+        config.validate()?;
+        Ok((config.with_overrides(), from_version))
+    }
+
+    /// 从(升级后的)配置表构造配置
+    ///
+    /// 自定义了 `[profiles]` 但没有 `default` 配置档且未指定 `profile` 时,
+    /// 使用按名称排序的第一个配置档, 而不是让整个文件因 `profile` 无效被拒绝
+    fn from_table(mut table: toml::Table) -> std::result::Result<Self, toml::de::Error> {
+        if !table.contains_key("profile")
+            && let Some(toml::Value::Table(profiles)) = table.get("profiles")
+            && !profiles.contains_key(&default_profile_name())
+            && let Some(first) = profiles.keys().next()
+        {
+            info!("未指定 profile, 使用第一个配置档: {}", first);
+            let first = toml::Value::String(first.clone());
+            table.insert("profile".to_string(), first);
+        }
+        Self::deserialize(toml::Value::Table(table))
+    }
+
+    /// 读取配置文件并升级到当前版本, 不叠加覆盖项
+    fn read_file_table(config_path: &Path) -> Result<toml::Table> {
+        let mut table = toml::from_str::<toml::Table>(&fs::read_to_string(config_path)?)?;
+        migrate(&mut table)?;
+        Ok(table)
+    }
+
+    fn to_table(&self) -> Result<toml::Table> {
+        match toml::Value::try_from(self)? {
+            toml::Value::Table(table) => Ok(table),
+            _ => Err(anyhow::anyhow!("配置无法转换为 TOML 表")),
+        }
+    }
+
+    /// 叠加环境变量和命令行参数中的覆盖项, 会使配置无效的覆盖项逐项跳过
+    fn with_overrides(self) -> Self {
+        let (config, _, rejected) = self.apply_overrides(overrides::get());
+        for error in rejected {
+            warn!("警告: {}", error);
+        }
+        config
+    }
+
+    /// 返回叠加后的配置、实际生效的覆盖项和被跳过的覆盖项的错误说明
+    fn apply_overrides(self, overrides: &Overrides) -> (Self, Overrides, Vec<String>) {
+        if overrides.is_empty() {
+            return (self, Overrides::default(), Vec::new());
+        }
+        let mut table = match self.to_table() {
+            Ok(table) => table,
+            Err(e) => {
+                let error = format!("无法应用环境变量或命令行参数中的配置: {}", e);
+                return (self, Overrides::default(), vec![error]);
+            }
+        };
+        let (applied, rejected) = overrides.apply_checked(&mut table, |table| {
+            Self::deserialize(toml::Value::Table(table.clone()))?.validate()?;
+            Ok(())
+        });
+        match Self::deserialize(toml::Value::Table(table)) {
+            Ok(config) => (config, applied, rejected),
+            Err(e) => {
+                let error = format!("无法应用环境变量或命令行参数中的配置: {}", e);
+                (self, Overrides::default(), vec![error])
+            }
+        }
+    }
+
+    /// 将配置文件复制为 `config.toml.<suffix>.bak`
+    fn backup(config_path: &Path, suffix: &str) -> Result<PathBuf> {
+        let mut name = config_path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("无效的配置文件路径: {:?}", config_path))?
+            .to_os_string();
+        name.push(format!(".{}.bak", suffix));
+        let backup_path = config_path.with_file_name(name);
+        fs::copy(config_path, &backup_path)?;
+        info!("已将配置文件备份到: {:?}", backup_path);
+        Ok(backup_path)
+    }
+
     /// 保存配置到指定文件
-    fn save_to_file(&self, config_path: &PathBuf) -> Result<()> {
-        let config_content = toml::to_string_pretty(self)?;
+    ///
+    /// 被环境变量或命令行参数覆盖的配置项保持文件中的原值
+    fn save_to_file(&self, config_path: &Path) -> Result<()> {
+        let overrides = overrides::get();
+        let config_content = if overrides.is_empty() {
+            toml::to_string_pretty(self)?
+        } else {
+            let mut table = self.to_table()?;
+            let file = Self::read_file_table(config_path).unwrap_or_default();
+            overrides.restore(&mut table, &file);
+            toml::to_string_pretty(&table)?
+        };
+        if let Some(config_dir) = config_path.parent() {
+            fs::create_dir_all(config_dir)?;
+        }
         fs::write(config_path, config_content)?;
         info!("配置已保存到: {:?}", config_path);
         Ok(())
     }
 }
 
+fn diff_tables(prefix: &str, old: &toml::Table, new: &toml::Table, changes: &mut Vec<String>) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) => {
+                diff_tables(&path, old, new, changes)
+            }
+            (Some(old), Some(new)) if old == new => {}
+            (Some(old), Some(new)) => changes.push(format!("{}: {} -> {}", path, old, new)),
+            (Some(old), None) => changes.push(format!("{}: {} -> (已删除)", path, old)),
+            (None, Some(new)) => changes.push(format!("{}: (新增) {}", path, new)),
+            (None, None) => {}
+        }
+    }
+}
+
+/// 监控配置文件, 文件被修改后把新配置或其中的错误发送给 `tx`
+///
+/// 程序自身保存配置也会触发一次通知, 由接收方比较差异后忽略。
+/// 文件有误期间拒绝保存配置, 以免覆盖用户正在编辑的内容
+pub fn watch_config(tx: Sender<std::result::Result<Config, ConfigErrors>>) -> Result<()> {
+    let config_path = Config::get_config_path()?;
+    let (event_tx, event_rx) = unbounded::<notify::Result<Event>>();
+    let mut watcher = PollWatcher::new(
+        move |res| {
+            if let Err(e) = event_tx.send(res) {
+                error!("监听配置文件通信异常: {:?}", e);
+            }
+        },
+        notify::Config::default().with_poll_interval(Duration::from_secs(1)),
+    )?;
+    watcher.watch(&config_path, RecursiveMode::NonRecursive)?;
+    info!("正在监控配置文件: {:?}", config_path);
+
+    for res in event_rx {
+        match res {
+            Ok(event) => {
+                if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
+                    continue;
+                }
+                let contents = match fs::read_to_string(&config_path) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        warn!("警告: 读取配置文件 {:?} 失败: {}", config_path, e);
+                        continue;
+                    }
+                };
+                match Config::parse(&contents) {
+                    Ok((config, _)) => {
+                        FILE_INVALID.store(false, Ordering::SeqCst);
+                        tx.send(Ok(config))?;
+                    }
+                    Err(errors) => {
+                        warn!("警告: 配置文件修改无效, 将继续使用当前配置: {}", errors);
+                        FILE_INVALID.store(true, Ordering::SeqCst);
+                        tx.send(Err(errors))?;
+                    }
+                }
+            }
+            Err(e) => error!("配置文件监控发生错误: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+/// 列出每个生效配置项的值及其来源, 用于 `hsarec config show`
+pub fn show_sources() -> Result<String> {
+    let config_path = Config::get_config_path()?;
+    let (file, config) = if config_path.exists() {
+        let file = Config::read_file_table(&config_path)?;
+        let config = Config::from_table(file.clone())?;
+        (file, config)
+    } else {
+        (toml::Table::new(), Config::default())
+    };
+    let (config, overrides, rejected) = config.apply_overrides(overrides::get());
+    let effective = config.to_table()?;
+
+    let mut output = format!("# 配置文件: {}\n", config_path.display());
+    for error in rejected {
+        output.push_str(&format!("# 警告: {}\n", error));
+    }
+    for (path, value) in overrides::leaves(&effective) {
+        output.push_str(&format!(
+            "{} = {}  # {}\n",
+            path.join("."),
+            value,
+            overrides.source_of(&path, &file)
+        ));
+    }
+    Ok(output)
+}
+
+/// 配置中指定的日志文件路径(含覆盖项), 在日志系统初始化之前读取, 因此出错时直接忽略
+pub fn log_file_override() -> Option<PathBuf> {
+    let config_path = Config::get_config_path().ok()?;
+    let mut table = Config::read_file_table(&config_path).unwrap_or_default();
+    overrides::get().apply(&mut table);
+    table
+        .get("log_file")?
+        .as_str()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
 static CONFIG: OnceLock<Arc<RwLock<Config>>> = OnceLock::new();
 
 /// 获取全局配置实例
@@ -106,3 +797,142 @@ pub fn get_config() -> Arc<RwLock<Config>> {
         })
         .clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_profile_uses_first_custom_profile() {
+        let contents = "version = 3\n[profiles.ranked]\nmax_per_game = 3\n[profiles.casual]\n";
+        let (config, _) = Config::parse(contents).unwrap();
+        assert_eq!(config.profile, "casual");
+        assert_eq!(config.profiles.len(), 2);
+    }
+
+    #[test]
+    fn unknown_profile_reports_profile_field() {
+        let contents = "version = 3\nprofile = \"ladder\"\n[profiles.ranked]\n";
+        let errors = Config::parse(contents).unwrap_err();
+        assert_eq!(errors.0.len(), 1);
+        assert_eq!(errors.0[0].field, "profile");
+    }
+
+    #[test]
+    fn v2_drops_auto_mode_and_its_hotkeys() {
+        let contents = "version = 2\nauto_mode = true\n[hotkeys]\n\"Shift+Alt+M\" = \"toggle-auto-mode\"\n\"Shift+Alt+R\" = \"disconnect\"\n";
+        let (config, from_version) = Config::parse(contents).unwrap();
+        assert_eq!(from_version, 2);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.extra.is_empty());
+        assert_eq!(
+            config.hotkeys,
+            BTreeMap::from([("Shift+Alt+R".to_string(), HotkeyAction::Disconnect)])
+        );
+    }
+
+    /// 在临时目录中写入一个配置文件, 返回其路径
+    fn write_temp_config(name: &str, contents: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("hsarec-config-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn v1_reconnect_hotkey_replaces_disconnect_binding() {
+        let mut table: toml::Table = toml::from_str(
+            "reconnect_hotkey = \"Ctrl+Alt+R\"\n[hotkeys]\n\"Shift+Alt+R\" = \"disconnect\"\n\"Shift+Alt+S\" = \"open-settings\"\n",
+        )
+        .unwrap();
+        assert_eq!(migrate(&mut table).unwrap(), 1);
+        assert_eq!(
+            table["version"],
+            toml::Value::Integer(CONFIG_VERSION.into())
+        );
+        assert!(!table.contains_key("reconnect_hotkey"));
+
+        let config = Config::from_table(table).unwrap();
+        assert_eq!(
+            config.hotkeys,
+            BTreeMap::from([
+                ("Ctrl+Alt+R".to_string(), HotkeyAction::Disconnect),
+                ("Shift+Alt+S".to_string(), HotkeyAction::OpenSettings),
+            ])
+        );
+    }
+
+    #[test]
+    fn v1_file_is_backed_up_and_upgraded() {
+        let contents = "reconnect_hotkey = \"Ctrl+Alt+R\"\nauto_mode = true\n";
+        let path = write_temp_config("v1", contents);
+        let config = Config::load_from_file(&path);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(
+            config.hotkey_for(HotkeyAction::Disconnect),
+            Some("Ctrl+Alt+R")
+        );
+        assert!(config.extra.is_empty());
+
+        assert_eq!(
+            fs::read_to_string(path.with_file_name("config.toml.v1.bak")).unwrap(),
+            contents
+        );
+        let (saved, from_version) = Config::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(from_version, CONFIG_VERSION);
+        assert_eq!(saved.hotkeys, config.hotkeys);
+    }
+
+    #[test]
+    fn unknown_keys_survive_save() {
+        let contents = "version = 3\nfuture_option = 1\n[future_table]\nname = \"x\"\n";
+        let path = write_temp_config("extra", contents);
+        let config = Config::load_from_file(&path);
+        assert_eq!(config.extra.len(), 2);
+
+        config.save_to_file(&path).unwrap();
+        let (saved, _) = Config::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.extra, config.extra);
+        assert_eq!(saved.extra["future_table"]["name"].as_str(), Some("x"));
+    }
+
+    #[test]
+    fn future_version_is_refused() {
+        let contents = format!("version = {}\nnew_option = true\n", CONFIG_VERSION + 1);
+        let errors = Config::parse(&contents).unwrap_err();
+        assert_eq!(errors.0.len(), 1);
+        assert_eq!(errors.0[0].field, "version");
+
+        let path = write_temp_config("future", &contents);
+        let config = Config::load_from_file(&path);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert!(config.save().is_err());
+    }
+
+    #[test]
+    fn broken_file_is_backed_up_and_not_overwritten() {
+        let contents = "version = 3\nhotkeys = [\n";
+        let path = write_temp_config("broken", contents);
+        let config = Config::load_from_file(&path);
+        assert_eq!(config.hotkeys, default_hotkeys());
+
+        let backups: Vec<String> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("config.toml.broken-") && name.ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            fs::read_to_string(path.with_file_name(&backups[0])).unwrap(),
+            contents
+        );
+
+        assert!(FILE_INVALID.load(Ordering::SeqCst));
+        assert!(config.save().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+    }
+}
@@ -0,0 +1,418 @@
+use anyhow::Result;
+use log::{error, info};
+use std::net::SocketAddrV4;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::DegradeConfig;
+
+/// 一次弱网模拟的参数
+#[derive(Debug, Clone)]
+pub struct DegradeParams {
+    pub delay_ms: u32,
+    pub loss_percent: f32,
+    pub duration: Duration,
+}
+
+impl From<&DegradeConfig> for DegradeParams {
+    fn from(config: &DegradeConfig) -> Self {
+        DegradeParams {
+            delay_ms: config.delay_ms,
+            loss_percent: config.loss_percent,
+            duration: Duration::from_secs(config.duration_secs),
+        }
+    }
+}
+
+/// 弱网模拟的具体实现, 负责对指定端点注入延迟/丢包以及撤销
+pub trait DegradeBackend: Send {
+    fn name(&self) -> &'static str;
+    fn apply(&mut self, endpoint: SocketAddrV4, params: &DegradeParams) -> Result<()>;
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// 根据当前平台和配置选择弱网模拟实现
+pub fn default_backend(config: &DegradeConfig) -> Box<dyn DegradeBackend> {
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(NetemBackend::new(config.interface.clone()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = config;
+        Box::new(UnsupportedBackend)
+    }
+}
+
+/// 管理弱网模拟的生命周期: 同一时间只允许一次模拟, 到期后自动恢复
+pub struct Degrader {
+    backend: Mutex<Box<dyn DegradeBackend>>,
+    active: AtomicBool,
+    /// 每次开始或撤销模拟时加一, 自动恢复的线程只撤销自己开始的那次模拟
+    generation: AtomicU64,
+}
+
+impl Degrader {
+    pub fn new(backend: Box<dyn DegradeBackend>) -> Arc<Self> {
+        Arc::new(Degrader {
+            backend: Mutex::new(backend),
+            active: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    /// 开始对 `endpoint` 进行弱网模拟, `params.duration` 后在后台线程中自动恢复
+    pub fn start(self: &Arc<Self>, endpoint: SocketAddrV4, params: DegradeParams) -> Result<()> {
+        anyhow::ensure!(
+            (0.0..=100.0).contains(&params.loss_percent),
+            "丢包率必须在 0 到 100 之间: {}",
+            params.loss_percent
+        );
+        if self.active.swap(true, Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("弱网模拟正在进行中, 请等待其结束。"));
+        }
+
+        let apply_result = self
+            .backend
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取弱网模拟锁: {}", e))
+            .and_then(|mut backend| {
+                info!(
+                    "[{}] 正在对 {} 注入 {}ms 延迟 / {}% 丢包, 持续 {:?}",
+                    backend.name(),
+                    endpoint,
+                    params.delay_ms,
+                    params.loss_percent,
+                    params.duration
+                );
+                if let Err(e) = backend.apply(endpoint, &params) {
+                    // 撤销已经部分生效的设置
+                    let _ = backend.clear();
+                    return Err(e);
+                }
+                Ok(self.generation.fetch_add(1, Ordering::SeqCst) + 1)
+            });
+        let generation = match apply_result {
+            Ok(generation) => generation,
+            Err(e) => {
+                self.active.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let degrader = Arc::clone(self);
+        std::thread::spawn(move || {
+            std::thread::sleep(params.duration);
+            if let Err(e) = degrader.stop_generation(Some(generation)) {
+                error!("恢复网络失败: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// 换用新的实现, 例如配置中的网卡发生变化; 正在进行的模拟会先用原来的实现撤销
+    pub fn set_backend(&self, backend: Box<dyn DegradeBackend>) -> Result<()> {
+        let mut current = self
+            .backend
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取弱网模拟锁: {}", e))?;
+        let result = if self.active.swap(false, Ordering::SeqCst) {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            current.clear()
+        } else {
+            Ok(())
+        };
+        *current = backend;
+        result
+    }
+
+    /// 立即撤销弱网模拟
+    pub fn stop(&self) -> Result<()> {
+        self.stop_generation(None)
+    }
+
+    /// 撤销弱网模拟; 指定 `generation` 时只在它仍是当前这次模拟时撤销
+    fn stop_generation(&self, generation: Option<u64>) -> Result<()> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut backend = self
+            .backend
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取弱网模拟锁: {}", e))?;
+        if generation.is_some_and(|generation| generation != self.generation.load(Ordering::SeqCst))
+            || !self.active.load(Ordering::SeqCst)
+        {
+            return Ok(());
+        }
+        backend.clear()?;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.active.store(false, Ordering::SeqCst);
+        info!("[{}] 已恢复网络。", backend.name());
+        Ok(())
+    }
+}
+
+/// 当前平台没有可用的弱网模拟实现
+#[cfg(not(target_os = "linux"))]
+pub struct UnsupportedBackend;
+
+#[cfg(not(target_os = "linux"))]
+impl DegradeBackend for UnsupportedBackend {
+    fn name(&self) -> &'static str {
+        "unsupported"
+    }
+
+    fn apply(&mut self, _endpoint: SocketAddrV4, _params: &DegradeParams) -> Result<()> {
+        Err(anyhow::anyhow!("当前平台不支持弱网模拟。"))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 基于 Linux `tc` + `netem` 的实现, 只对发往目标端点的流量生效
+///
+/// 不改动网卡上已有的根队列(例如 SQM 配置的 cake): 在网卡的 clsact 出口上挂一个过滤器,
+/// 把匹配目标地址和端口的流量重定向到专用的 ifb 网卡, netem 挂在 ifb 网卡的根队列上
+#[cfg(target_os = "linux")]
+pub struct NetemBackend {
+    interface: Option<String>,
+    applied: Option<NetemApplied>,
+}
+
+/// 已经生效的设置, 撤销时只删除本程序添加的部分
+#[cfg(target_os = "linux")]
+struct NetemApplied {
+    dev: String,
+    /// clsact 是否由本程序添加; 网卡上原本就有 clsact 时只删除自己的过滤器
+    added_clsact: bool,
+    filter_added: bool,
+}
+
+/// 承载 netem 的 ifb 网卡名称
+#[cfg(target_os = "linux")]
+const IFB_DEVICE: &str = "hsarec-ifb";
+/// 过滤器的优先级, 用于撤销时只删除自己添加的过滤器
+#[cfg(target_os = "linux")]
+const FILTER_PREF: &str = "49";
+
+#[cfg(target_os = "linux")]
+impl NetemBackend {
+    pub fn new(interface: Option<String>) -> Self {
+        NetemBackend {
+            interface,
+            applied: None,
+        }
+    }
+
+    /// 通过 `ip route get` 查询到达目标地址所用的网卡
+    fn route_interface(endpoint: SocketAddrV4) -> Result<String> {
+        let stdout = Self::run("ip", &["route", "get", &endpoint.ip().to_string()])?;
+        let words: Vec<&str> = stdout.split_whitespace().collect();
+        words
+            .windows(2)
+            .find(|pair| pair[0] == "dev")
+            .map(|pair| pair[1].to_string())
+            .ok_or_else(|| anyhow::anyhow!("无法从路由信息中找到网卡: {}", stdout.trim()))
+    }
+
+    fn run(program: &str, args: &[&str]) -> Result<String> {
+        let output = std::process::Command::new(program).args(args).output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "执行 {} {} 失败: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn tc(args: &[&str]) -> Result<()> {
+        Self::run("tc", args).map(|_| ())
+    }
+
+    /// 网卡上已有的 ingress 队列与 clsact 互斥, 此时无法在不改动它的情况下挂载过滤器
+    fn ensure_clsact(dev: &str) -> Result<bool> {
+        let qdiscs = Self::run("tc", &["qdisc", "show", "dev", dev])?;
+        let kinds: Vec<&str> = qdiscs
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .collect();
+        if kinds.contains(&"clsact") {
+            return Ok(false);
+        }
+        anyhow::ensure!(
+            !kinds.contains(&"ingress"),
+            "网卡 {} 上已配置 ingress 队列, 为避免覆盖现有配置, 无法进行弱网模拟",
+            dev
+        );
+        Self::tc(&["qdisc", "add", "dev", dev, "clsact"])?;
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl DegradeBackend for NetemBackend {
+    fn name(&self) -> &'static str {
+        "netem"
+    }
+
+    fn apply(&mut self, endpoint: SocketAddrV4, params: &DegradeParams) -> Result<()> {
+        let dev = match &self.interface {
+            Some(dev) if !dev.is_empty() => dev.clone(),
+            _ => Self::route_interface(endpoint)?,
+        };
+        let delay = format!("{}ms", params.delay_ms);
+        let loss = format!("{}%", params.loss_percent);
+        let dst = format!("{}/32", endpoint.ip());
+        let dport = endpoint.port().to_string();
+
+        // 上次异常退出时可能残留 ifb 网卡
+        let _ = Self::run("ip", &["link", "del", IFB_DEVICE]);
+        Self::run("ip", &["link", "add", IFB_DEVICE, "type", "ifb"])?;
+        let added_clsact = match Self::ensure_clsact(&dev) {
+            Ok(added) => added,
+            Err(e) => {
+                let _ = Self::run("ip", &["link", "del", IFB_DEVICE]);
+                return Err(e);
+            }
+        };
+        let applied = self.applied.insert(NetemApplied {
+            dev: dev.clone(),
+            added_clsact,
+            filter_added: false,
+        });
+
+        Self::run("ip", &["link", "set", IFB_DEVICE, "up"])?;
+        Self::tc(&[
+            "qdisc", "add", "dev", IFB_DEVICE, "root", "netem", "delay", &delay, "loss", &loss,
+        ])?;
+        Self::tc(&[
+            "filter", "add", "dev", &dev, "egress", "pref", FILTER_PREF, "protocol", "ip", "u32",
+            "match", "ip", "dst", &dst, "match", "ip", "dport", &dport, "0xffff", "action",
+            "mirred", "egress", "redirect", "dev", IFB_DEVICE,
+        ])?;
+        applied.filter_added = true;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        let Some(applied) = self.applied.take() else {
+            return Ok(());
+        };
+        let dev = applied.dev.as_str();
+        let result = if applied.added_clsact {
+            Self::tc(&["qdisc", "del", "dev", dev, "clsact"])
+        } else if applied.filter_added {
+            Self::tc(&["filter", "del", "dev", dev, "egress", "pref", FILTER_PREF])
+        } else {
+            Ok(())
+        };
+        // 先撤销重定向再删除 ifb 网卡, 避免流量被重定向到不存在的网卡
+        result.and(Self::run("ip", &["link", "del", IFB_DEVICE]).map(|_| ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// 记录调用的实现, `fail_apply` 为真时注入失败
+    struct FakeBackend {
+        calls: Arc<Mutex<Vec<&'static str>>>,
+        fail_apply: bool,
+    }
+
+    impl DegradeBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn apply(&mut self, _endpoint: SocketAddrV4, _params: &DegradeParams) -> Result<()> {
+            self.calls.lock().unwrap().push("apply");
+            anyhow::ensure!(!self.fail_apply, "注入失败");
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<()> {
+            self.calls.lock().unwrap().push("clear");
+            Ok(())
+        }
+    }
+
+    fn degrader(fail_apply: bool) -> (Arc<Degrader>, Arc<Mutex<Vec<&'static str>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let backend = FakeBackend {
+            calls: Arc::clone(&calls),
+            fail_apply,
+        };
+        (Degrader::new(Box::new(backend)), calls)
+    }
+
+    fn params(duration: Duration) -> DegradeParams {
+        DegradeParams {
+            delay_ms: 100,
+            loss_percent: 10.0,
+            duration,
+        }
+    }
+
+    const ENDPOINT: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 3724);
+    const LONG: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn start_rejects_second_run_until_stopped() {
+        let (degrader, calls) = degrader(false);
+        degrader.start(ENDPOINT, params(LONG)).unwrap();
+        assert!(degrader.start(ENDPOINT, params(LONG)).is_err());
+        degrader.stop().unwrap();
+        degrader.stop().unwrap();
+        degrader.start(ENDPOINT, params(LONG)).unwrap();
+        assert_eq!(*calls.lock().unwrap(), ["apply", "clear", "apply"]);
+    }
+
+    #[test]
+    fn failed_apply_is_undone() {
+        let (degrader, calls) = degrader(true);
+        assert!(degrader.start(ENDPOINT, params(LONG)).is_err());
+        assert!(!degrader.active.load(Ordering::SeqCst));
+        assert_eq!(*calls.lock().unwrap(), ["apply", "clear"]);
+        // 没有残留的进行中状态, 可以再次尝试
+        assert!(degrader.start(ENDPOINT, params(LONG)).is_err());
+        assert_eq!(calls.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn timer_restores_network() {
+        let (degrader, calls) = degrader(false);
+        degrader.start(ENDPOINT, params(Duration::from_millis(20))).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(!degrader.active.load(Ordering::SeqCst));
+        assert_eq!(*calls.lock().unwrap(), ["apply", "clear"]);
+    }
+
+    #[test]
+    fn stale_timer_leaves_new_run_alone() {
+        let (degrader, calls) = degrader(false);
+        degrader.start(ENDPOINT, params(Duration::from_millis(100))).unwrap();
+        degrader.stop().unwrap();
+        degrader.start(ENDPOINT, params(LONG)).unwrap();
+        std::thread::sleep(Duration::from_millis(400));
+        assert!(degrader.active.load(Ordering::SeqCst));
+        assert_eq!(*calls.lock().unwrap(), ["apply", "clear", "apply"]);
+    }
+
+    #[test]
+    fn invalid_loss_is_rejected() {
+        let (degrader, calls) = degrader(false);
+        let mut params = params(LONG);
+        params.loss_percent = 150.0;
+        assert!(degrader.start(ENDPOINT, params).is_err());
+        assert!(calls.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use log::{info, warn};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+/// 查询当前前台窗口所属进程的 PID, 无法确定时返回 `None`
+pub trait ForegroundProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn foreground_pid(&self) -> Option<u32>;
+}
+
+/// 根据当前平台选择前台窗口查询实现
+pub fn default_provider() -> Box<dyn ForegroundProvider> {
+    #[cfg(windows)]
+    {
+        Box::new(WindowsForeground)
+    }
+    #[cfg(not(windows))]
+    {
+        Box::new(XdotoolForeground)
+    }
+}
+
+/// 焦点保护: 只有前台窗口属于炉石时才执行热键动作, 避免在聊天软件等窗口中误触
+pub struct FocusGuard {
+    provider: Box<dyn ForegroundProvider>,
+    /// 炉石进程的 PID, 由定时刷新游戏状态时更新, 热键回调中不查找进程
+    hs_pid: Mutex<Option<u32>>,
+    /// 上次查询时是否无法获取前台窗口, 只在状态变化时记录日志
+    unavailable: AtomicBool,
+}
+
+impl FocusGuard {
+    pub fn new(provider: Box<dyn ForegroundProvider>) -> Self {
+        FocusGuard {
+            provider,
+            hs_pid: Mutex::new(None),
+            unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// 更新炉石进程的 PID, 炉石没有运行时为 `None`
+    pub fn set_game_pid(&self, pid: Option<u32>) {
+        if let Ok(mut hs_pid) = self.hs_pid.lock() {
+            *hs_pid = pid;
+        }
+    }
+
+    /// 当前能否获取前台窗口, 不能时焦点保护不起作用
+    pub fn is_available(&self) -> bool {
+        self.foreground_pid().is_some()
+    }
+
+    /// 前台窗口是否属于炉石进程
+    ///
+    /// 无法确定前台窗口时放行, 以免查询失败导致热键完全失效
+    pub fn is_game_focused(&self) -> bool {
+        let Some(foreground) = self.foreground_pid() else {
+            return true;
+        };
+        self.hs_pid
+            .lock()
+            .map_or(true, |hs_pid| *hs_pid == Some(foreground))
+    }
+
+    /// 查询前台窗口所属进程, 只在能否获取发生变化时记录日志
+    fn foreground_pid(&self) -> Option<u32> {
+        let pid = self.provider.foreground_pid();
+        let unavailable = pid.is_none();
+        if self.unavailable.swap(unavailable, Ordering::Relaxed) != unavailable {
+            if unavailable {
+                warn!(
+                    "[{}] 无法获取前台窗口所属进程, 焦点保护暂不可用。",
+                    self.provider.name()
+                );
+            } else {
+                info!(
+                    "[{}] 已能获取前台窗口, 焦点保护恢复生效。",
+                    self.provider.name()
+                );
+            }
+        }
+        pid
+    }
+}
+
+/// 通过 `GetForegroundWindow` 获取前台窗口
+#[cfg(windows)]
+pub struct WindowsForeground;
+
+#[cfg(windows)]
+impl ForegroundProvider for WindowsForeground {
+    fn name(&self) -> &'static str {
+        "win32"
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        let mut pid = 0u32;
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_invalid() {
+                return None;
+            }
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        }
+        (pid != 0).then_some(pid)
+    }
+}
+
+/// 通过 `xdotool` 获取 X11 下的前台窗口, Wayland 下通常无法获取
+#[cfg(not(windows))]
+pub struct XdotoolForeground;
+
+#[cfg(not(windows))]
+impl ForegroundProvider for XdotoolForeground {
+    fn name(&self) -> &'static str {
+        "xdotool"
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        let output = std::process::Command::new("xdotool")
+            .args(["getactivewindow", "getwindowpid"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+}
+
+/// 固定返回给定 PID 的实现, 用于测试焦点判断逻辑
+#[cfg(test)]
+pub struct FixedForeground(pub Option<u32>);
+
+#[cfg(test)]
+impl ForegroundProvider for FixedForeground {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(foreground: Option<u32>, game: Option<u32>) -> FocusGuard {
+        let guard = FocusGuard::new(Box::new(FixedForeground(foreground)));
+        guard.set_game_pid(game);
+        guard
+    }
+
+    #[test]
+    fn allows_when_game_is_focused() {
+        assert!(guard(Some(42), Some(42)).is_game_focused());
+    }
+
+    #[test]
+    fn blocks_other_windows() {
+        assert!(!guard(Some(7), Some(42)).is_game_focused());
+        assert!(!guard(Some(7), None).is_game_focused());
+    }
+
+    #[test]
+    fn allows_without_foreground_window() {
+        assert!(guard(None, Some(42)).is_game_focused());
+        assert!(guard(None, None).is_game_focused());
+    }
+
+    #[test]
+    fn reports_availability() {
+        assert!(guard(Some(7), None).is_available());
+        let guard = guard(None, Some(42));
+        assert!(!guard.is_available());
+        // 多次查询失败时保持不可用
+        assert!(guard.is_game_focused());
+        assert!(!guard.is_available());
+    }
+
+    #[test]
+    fn follows_game_restarts() {
+        let guard = guard(Some(43), Some(42));
+        assert!(!guard.is_game_focused());
+        guard.set_game_pid(Some(43));
+        assert!(guard.is_game_focused());
+    }
+}
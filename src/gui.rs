@@ -1,10 +1,238 @@
+use slint::platform::Key as SlintKey;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::status::{Outcome, StatusSnapshot};
+use crate::{HistoryRow, SettingsWindow, StatusWindow};
+use crate::config::{Config, DisconnectStrategy, Language, Profile, ValidationError};
+use crate::hotkey::{self, Hotkey, HotkeyAction, HotkeySequence};
+
 #[derive(Debug, Clone)]
 pub enum GuiOutMessage {
-    SaveHotKeys(String),
+    /// 保存某个动作的热键
+    SaveHotKeys(HotkeyAction, String),
+    /// 保存当前配置档的拔线设置
+    SaveProfile(Profile),
+    /// 保存"通用"页的设置
+    SaveGeneral(GeneralSettings),
+    /// 状态窗口请求刷新
+    RefreshStatus,
+}
+
+/// 设置窗口"通用"页的内容
+#[derive(Debug, Clone)]
+pub struct GeneralSettings {
+    pub log_file: Option<PathBuf>,
+    pub auto_start: bool,
+    pub exit_with_game: bool,
+    pub notifications: bool,
+    pub language: Language,
+}
+
+impl GeneralSettings {
+    /// 写入配置, 返回界面语言是否发生变化
+    pub fn apply(self, config: &mut Config) -> bool {
+        let language_changed = config.language != self.language;
+        config.log_file = self.log_file;
+        config.auto_start = self.auto_start;
+        config.exit_with_game = self.exit_with_game;
+        config.notifications.enabled = self.notifications;
+        config.language = self.language;
+        language_changed
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum GuiInMessage {
     Exit,
     Show,
+    ShowStatus(String),
+    /// 配置文件在外部被修改
+    ConfigChanged(Config),
+    /// 配置文件有误, 弹窗列出全部错误
+    ConfigErrors(Vec<ValidationError>),
+    ShowStatusWindow,
+    /// 状态窗口的最新内容
+    StatusUpdated(StatusSnapshot),
+}
+
+/// 将 Slint 按键事件的文本转换为热键语法中的按键名称, 无法识别时返回空字符串
+pub fn key_name(text: &str) -> String {
+    let mut chars = text.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return String::new();
+    };
+
+    let f1 = char::from(SlintKey::F1) as u32;
+    if (f1..f1 + 24).contains(&(c as u32)) {
+        return format!("F{}", c as u32 - f1 + 1);
+    }
+    let special = [
+        (SlintKey::UpArrow, "Up"),
+        (SlintKey::DownArrow, "Down"),
+        (SlintKey::LeftArrow, "Left"),
+        (SlintKey::RightArrow, "Right"),
+        (SlintKey::Home, "Home"),
+        (SlintKey::End, "End"),
+        (SlintKey::PageUp, "PageUp"),
+        (SlintKey::PageDown, "PageDown"),
+        (SlintKey::Insert, "Insert"),
+        (SlintKey::Delete, "Delete"),
+        (SlintKey::Backspace, "Backspace"),
+        (SlintKey::Tab, "Tab"),
+        (SlintKey::Return, "Enter"),
+        (SlintKey::Space, "Space"),
+    ];
+    if let Some((_, name)) = special.iter().find(|(key, _)| char::from(*key) == c) {
+        return name.to_string();
+    }
+
+    match c.to_string().parse::<Hotkey>() {
+        Ok(hotkey) if hotkey.modifiers.is_empty() => hotkey.key.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// 校验热键或按键序列, 合法时返回空字符串, 否则返回错误信息
+pub fn validate_hotkey(text: &str) -> String {
+    match text.parse::<HotkeySequence>() {
+        Ok(_) => String::new(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// 检查把热键绑定到 `action` 时的冲突, 没有冲突时返回空字符串, 否则返回提示信息
+pub fn hotkey_conflicts(
+    text: &str,
+    action: HotkeyAction,
+    hotkeys: &BTreeMap<String, HotkeyAction>,
+) -> String {
+    let Ok(sequence) = text.parse::<HotkeySequence>() else {
+        return String::new();
+    };
+    hotkey::find_conflicts(&sequence, action, hotkeys)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// 设置窗口动作下拉框的选中项对应的动作
+pub fn hotkey_action(index: i32) -> HotkeyAction {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| HotkeyAction::ALL.get(index).copied())
+        .unwrap_or(HotkeyAction::Disconnect)
+}
+
+/// 用配置填充设置窗口
+pub fn load_settings(window: &SettingsWindow, config: &Config) {
+    let hotkeys: Vec<slint::SharedString> = HotkeyAction::ALL
+        .iter()
+        .map(|action| config.hotkey_for(*action).unwrap_or_default().into())
+        .collect();
+    window.set_hotkeys(slint::ModelRc::new(slint::VecModel::from(hotkeys)));
+
+    let profile = config.active_profile();
+    window.set_profile(config.profile.clone().into());
+    window.set_strategy(match profile.strategy {
+        DisconnectStrategy::Server => crate::DisconnectStrategy::Server,
+        DisconnectStrategy::All => crate::DisconnectStrategy::All,
+    });
+    window.set_cooldown_secs(i32::try_from(profile.cooldown_secs).unwrap_or(i32::MAX));
+    window.set_max_per_game(i32::try_from(profile.max_per_game).unwrap_or(i32::MAX));
+
+    window.set_log_file(
+        config
+            .log_file
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    window.set_auto_start(config.auto_start);
+    window.set_exit_with_game(config.exit_with_game);
+    window.set_notifications(config.notifications.enabled);
+    window.set_language(match config.language {
+        Language::ZhCn => crate::Language::ZhCn,
+        Language::En => crate::Language::En,
+    });
+}
+
+/// 读取设置窗口"拔线"页的内容
+pub fn profile_settings(window: &SettingsWindow) -> Profile {
+    Profile {
+        strategy: match window.get_strategy() {
+            crate::DisconnectStrategy::Server => DisconnectStrategy::Server,
+            crate::DisconnectStrategy::All => DisconnectStrategy::All,
+        },
+        cooldown_secs: u64::try_from(window.get_cooldown_secs()).unwrap_or_default(),
+        max_per_game: u32::try_from(window.get_max_per_game()).unwrap_or_default(),
+    }
+}
+
+/// 读取设置窗口"通用"页的内容
+pub fn general_settings(window: &SettingsWindow) -> GeneralSettings {
+    let log_file = window.get_log_file().trim().to_string();
+    GeneralSettings {
+        log_file: (!log_file.is_empty()).then(|| PathBuf::from(log_file)),
+        auto_start: window.get_auto_start(),
+        exit_with_game: window.get_exit_with_game(),
+        notifications: window.get_notifications(),
+        language: match window.get_language() {
+            crate::Language::ZhCn => Language::ZhCn,
+            crate::Language::En => Language::En,
+        },
+    }
+}
+
+/// 切换界面语言
+pub fn select_language(language: Language) {
+    if let Err(e) = slint::select_bundled_translation(language.locale()) {
+        log::warn!("警告: 无法切换界面语言 {:?}: {}", language, e);
+    }
+}
+
+/// 用最新状态填充状态窗口
+pub fn show_status(window: &StatusWindow, snapshot: &StatusSnapshot) {
+    window.set_pid(
+        match &snapshot.pid {
+            Ok(pid) => pid.to_string(),
+            Err(e) => e.clone(),
+        }
+        .into(),
+    );
+    window.set_server(
+        snapshot
+            .server
+            .map(|server| server.to_string())
+            .unwrap_or_else(|| "未检测到".to_string())
+            .into(),
+    );
+    window.set_state(snapshot.state.clone().into());
+    window.set_warning(snapshot.warning.clone().unwrap_or_default().into());
+
+    let connections: Vec<slint::SharedString> = snapshot
+        .connections
+        .iter()
+        .map(|connection| connection.into())
+        .collect();
+    window.set_connections(slint::ModelRc::new(slint::VecModel::from(connections)));
+
+    let history: Vec<HistoryRow> = snapshot
+        .history
+        .iter()
+        .map(|record| HistoryRow {
+            time: record.time.clone().into(),
+            action: record.action.clone().into(),
+            duration: record
+                .duration()
+                .map(|duration| format!("{:.1}s", duration.as_secs_f32()))
+                .unwrap_or_else(|| "-".to_string())
+                .into(),
+            outcome: record.outcome.to_string().into(),
+            failed: matches!(record.outcome, Outcome::Failed(_)),
+        })
+        .collect();
+    window.set_history(slint::ModelRc::new(slint::VecModel::from(history)));
 }
@@ -12,9 +12,13 @@ use std::path::PathBuf;
 
 use crate::LOGFILE_NAME;
 use crate::PROCESS_NAME;
+use crate::helper::ConnectionCloser;
 use crate::network;
 use crate::process;
 
+/// 检测炉石进程启动和退出的间隔
+const PROCESS_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
 #[derive(Debug)]
 pub struct LogMessage {
     pub ip: Option<Ipv4Addr>,
@@ -33,26 +37,10 @@ pub fn watch_log(log_tx: Sender<LogMessage>) -> anyhow::Result<()> {
         Config::default().with_poll_interval(std::time::Duration::from_secs(1)),
     )?;
 
-    let process_name = PROCESS_NAME;
-    let data = process::get_process_by_name(process_name)?;
-    if data.is_empty() {
-        return Err(anyhow::anyhow!("没有找到名为 {} 的进程。", process_name));
-    }
-    if data.len() > 1 {
-        return Err(anyhow::anyhow!(
-            "找到多个名为 {} 的进程: {:?}",
-            process_name,
-            data
-        ));
-    }
-    let path = data
-        .get(0)
-        .map(|p| p.path.clone())
-        .ok_or_else(|| anyhow::anyhow!("无法获取进程 {} 的路径。", process_name))?;
-
+    let path = find_process()?.path;
     let path = path
         .parent()
-        .ok_or_else(|| anyhow::anyhow!("无法获取进程 {} 的日志文件路径。", process_name))?
+        .ok_or_else(|| anyhow::anyhow!("无法获取进程 {} 的日志文件路径。", PROCESS_NAME))?
         .join("Logs");
 
     let path = get_newest_folder(&path.to_string_lossy())?
@@ -89,12 +77,10 @@ pub fn watch_log(log_tx: Sender<LogMessage>) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn reconnect(ip: Option<Ipv4Addr>, port: Option<u16>) -> anyhow::Result<()> {
+/// 查找唯一的炉石进程
+fn find_process() -> anyhow::Result<process::ProcessInfo> {
     let process_name = PROCESS_NAME;
-    let data = process::get_process_by_name(process_name)?;
-    if data.is_empty() {
-        return Err(anyhow::anyhow!("没有找到名为 {} 的进程。", process_name));
-    }
+    let mut data = process::get_process_by_name(process_name)?;
     if data.len() > 1 {
         return Err(anyhow::anyhow!(
             "找到多个名为 {} 的进程: {:?}",
@@ -102,10 +88,34 @@ pub fn reconnect(ip: Option<Ipv4Addr>, port: Option<u16>) -> anyhow::Result<()>
             data
         ));
     }
-    let pid = data
-        .get(0)
-        .map(|p| p.pid)
-        .ok_or_else(|| anyhow::anyhow!("无法获取进程 {} 的 PID。", process_name))?;
+    data.pop()
+        .ok_or_else(|| anyhow::anyhow!("没有找到名为 {} 的进程。", process_name))
+}
+
+/// 查找唯一的炉石进程的 PID
+pub fn find_pid() -> anyhow::Result<u32> {
+    Ok(find_process()?.pid)
+}
+
+/// 在当前线程中定时查找炉石进程, PID 变化(包括游戏启动和退出)时发送新的 PID, 接收端关闭后返回
+pub fn watch_process(pid_tx: Sender<Option<u32>>) {
+    let mut last = None;
+    loop {
+        let pid = find_pid().ok();
+        if last != Some(pid) {
+            if pid_tx.send(pid).is_err() {
+                return;
+            }
+            last = Some(pid);
+        }
+        std::thread::sleep(PROCESS_POLL_INTERVAL);
+    }
+}
+
+/// 获取炉石进程当前的全部网络连接
+pub fn get_connections() -> anyhow::Result<Vec<network::NetworkInfo>> {
+    let process_name = PROCESS_NAME;
+    let pid = find_pid()?;
     let data = network::get_process_by_pid(pid)?;
     if data.is_empty() {
         return Err(anyhow::anyhow!(
@@ -116,27 +126,62 @@ pub fn reconnect(ip: Option<Ipv4Addr>, port: Option<u16>) -> anyhow::Result<()>
     }
     let connections: Vec<String> = data.iter().map(|p| format!("{}", p)).collect();
     info!("获取到的网络信息: {}", connections.join(", "));
+    Ok(data)
+}
 
-    data.iter()
-        .find(|info| {
-            if let (Some(ip), Some(port)) = (ip, port) {
-                info.remote_addr_as_ipv4() == ip && info.remote_port_as_u16() == port
-            } else {
-                false
-            }
-        })
-        .map_or_else(
-            || Err(anyhow::anyhow!("没有找到匹配的网络信息。")),
-            |info| {
-                info!(
-                    "正在关闭炉石网络连接 {}:{}",
-                    info.remote_addr_as_ipv4(),
-                    info.remote_port_as_u16()
-                );
-                network::close_tcp_connection(info)?;
-                Ok(())
-            },
-        )?;
+/// 断开日志中记录的游戏服务器连接
+pub fn reconnect(
+    ip: Option<Ipv4Addr>,
+    port: Option<u16>,
+    closer: &dyn ConnectionCloser,
+) -> anyhow::Result<()> {
+    let data = get_connections()?;
+
+    let matched = data.iter().find(|info| {
+        if let (Some(ip), Some(port)) = (ip, port) {
+            info.remote_addr_as_ipv4() == ip && info.remote_port_as_u16() == port
+        } else {
+            false
+        }
+    });
+    match matched {
+        Some(info) => {
+            info!(
+                "正在关闭炉石网络连接 {}:{}",
+                info.remote_addr_as_ipv4(),
+                info.remote_port_as_u16()
+            );
+            closer.close(info)?;
+        }
+        None => return Err(anyhow::anyhow!("没有找到匹配的网络信息。")),
+    }
+    Ok(())
+}
+
+/// 断开炉石进程的全部网络连接
+pub fn reconnect_all(closer: &dyn ConnectionCloser) -> anyhow::Result<()> {
+    let data = get_connections()?;
+    close_all(&data, closer)
+}
+
+fn close_all(data: &[network::NetworkInfo], closer: &dyn ConnectionCloser) -> anyhow::Result<()> {
+    let mut failed = 0;
+    for info in data {
+        info!(
+            "正在关闭炉石网络连接 {}:{}",
+            info.remote_addr_as_ipv4(),
+            info.remote_port_as_u16()
+        );
+        if let Err(e) = closer.close(info) {
+            error!("关闭连接 {} 失败: {}", info, e);
+            failed += 1;
+        }
+    }
+    anyhow::ensure!(
+        failed < data.len(),
+        "全部 {} 个连接都关闭失败。",
+        data.len()
+    );
     Ok(())
 }
 
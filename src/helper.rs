@@ -0,0 +1,214 @@
+use anyhow::Result;
+use std::ffi::OsString;
+
+use crate::network::{self, NetworkInfo};
+
+#[cfg(any(windows, test))]
+mod client;
+#[cfg(windows)]
+mod pipe;
+#[cfg(any(windows, test))]
+mod protocol;
+
+/// 当前进程是否有管理员权限, Linux 下为有效用户是否为 root
+pub fn is_elevated() -> bool {
+    #[cfg(windows)]
+    {
+        is_elevated::is_elevated()
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| {
+                let uids = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+                uids.split_whitespace().nth(1).map(|euid| euid == "0")
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// 关闭 TCP 连接的方式
+///
+/// 关闭其他进程的连接需要管理员权限: 已提权时直接关闭, 否则交给以管理员权限运行的辅助进程
+pub trait ConnectionCloser: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// 为假时无法关闭任何连接, 程序以降级模式运行
+    fn is_available(&self) -> bool {
+        true
+    }
+    /// 与 `network::close_tcp_connection` 语义相同
+    fn close(&self, info: &NetworkInfo) -> Result<()>;
+}
+
+/// 当前进程已提权, 直接关闭连接
+pub struct DirectCloser;
+
+impl ConnectionCloser for DirectCloser {
+    fn name(&self) -> &'static str {
+        "direct"
+    }
+
+    fn close(&self, info: &NetworkInfo) -> Result<()> {
+        network::close_tcp_connection(info)
+    }
+}
+
+/// 无法关闭连接时使用, 每次都返回无法关闭的原因
+#[cfg(not(windows))]
+pub struct UnavailableCloser(pub String);
+
+#[cfg(not(windows))]
+impl ConnectionCloser for UnavailableCloser {
+    fn name(&self) -> &'static str {
+        "unavailable"
+    }
+
+    fn is_available(&self) -> bool {
+        false
+    }
+
+    fn close(&self, _info: &NetworkInfo) -> Result<()> {
+        anyhow::bail!("没有管理员权限, 无法关闭连接: {}", self.0)
+    }
+}
+
+/// 按当前权限选择关闭连接的方式
+///
+/// Windows 下未提权时在第一次关闭连接时才启动辅助进程, 开机自启动时不会弹出 UAC 授权窗口;
+/// 其他平台没有辅助进程, 需要以 root 权限运行
+pub fn default_closer() -> Box<dyn ConnectionCloser> {
+    if is_elevated() {
+        return Box::new(DirectCloser);
+    }
+    #[cfg(windows)]
+    {
+        Box::new(client::HelperClient::new(pipe::PipeLauncher))
+    }
+    #[cfg(not(windows))]
+    {
+        Box::new(UnavailableCloser("请以 root 权限运行本程序".to_string()))
+    }
+}
+
+/// 以管理员权限启动本程序, `hidden` 为真时不显示窗口
+///
+/// Windows 下用户拒绝 UAC 授权时返回错误, 成功时返回新进程的句柄; Linux 下通过 `pkexec` 启动
+#[cfg(windows)]
+fn spawn_elevated(args: &[OsString], hidden: bool) -> Result<std::os::windows::io::OwnedHandle> {
+    use std::os::windows::io::FromRawHandle;
+    use windows::Win32::UI::Shell::{
+        SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{SW_HIDE, SW_SHOWNORMAL};
+    use windows::core::{HSTRING, PCWSTR, w};
+
+    let exe = HSTRING::from(std::env::current_exe()?.as_os_str());
+    let params = HSTRING::from(
+        args.iter()
+            .map(|arg| quote_arg(&arg.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    let show = if hidden { SW_HIDE } else { SW_SHOWNORMAL };
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(exe.as_ptr()),
+        lpParameters: PCWSTR(params.as_ptr()),
+        nShow: show.0,
+        ..Default::default()
+    };
+    // 用户拒绝 UAC 授权时同样返回错误
+    unsafe { ShellExecuteExW(&mut info) }
+        .map_err(|e| anyhow::anyhow!("无法以管理员权限启动: {}", e))?;
+    anyhow::ensure!(!info.hProcess.is_invalid(), "无法获取新进程的句柄");
+    Ok(unsafe { std::os::windows::io::OwnedHandle::from_raw_handle(info.hProcess.0) })
+}
+
+/// 按 `CommandLineToArgvW` 的规则转义一个命令行参数
+///
+/// 只有引号前和参数末尾(紧接着结尾的引号)的反斜杠需要加倍
+#[cfg(any(windows, test))]
+pub fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0b', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(target_os = "linux")]
+fn spawn_elevated(args: &[OsString], _hidden: bool) -> Result<std::process::Child> {
+    let child = std::process::Command::new("pkexec")
+        .arg(std::env::current_exe()?)
+        .args(args)
+        .spawn()?;
+    Ok(child)
+}
+
+/// 以管理员权限重新启动本程序, 沿用当前的命令行参数; 成功后调用方应退出
+pub fn restart_elevated() -> Result<()> {
+    // 一次性的操作已经执行过, 不再交给新实例
+    const ONE_SHOT_ARGS: [&str; 3] = ["--disconnect", "--degrade", "--show-settings"];
+    let mut args: Vec<OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| !ONE_SHOT_ARGS.iter().any(|one_shot| arg == one_shot))
+        .collect();
+    // 新实例等待本实例退出后再获取单实例锁
+    if !args.iter().any(|arg| arg == "--wait-for-lock") {
+        args.push("--wait-for-lock".into());
+    }
+    spawn_elevated(&args, false)?;
+    Ok(())
+}
+
+/// 辅助进程入口: 连接界面进程创建的命名管道, 管道断开后退出
+pub fn run(pipe_name: &str, token: &str) -> Result<()> {
+    anyhow::ensure!(is_elevated(), "辅助进程需要以管理员权限运行");
+    #[cfg(windows)]
+    {
+        pipe::run(pipe_name, token)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (pipe_name, token);
+        anyhow::bail!("当前平台不需要辅助进程")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_arg_follows_command_line_to_argv_rules() {
+        assert_eq!(quote_arg("--wait-for-lock"), "--wait-for-lock");
+        assert_eq!(quote_arg(r"C:\hsarec\config.toml"), r"C:\hsarec\config.toml");
+        assert_eq!(quote_arg(""), r#""""#);
+        assert_eq!(quote_arg("a b"), r#""a b""#);
+        assert_eq!(quote_arg(r"C:\my dir\"), r#""C:\my dir\\""#);
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote_arg(r"a\\b c"), r#""a\\b c""#);
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use log::{error, warn};
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+
+use super::{ConnectionCloser, protocol};
+use crate::network::NetworkInfo;
+
+/// 启动辅助进程并返回认证后的连接
+pub trait Launcher: Send + Sync {
+    fn launch(&self) -> Result<Connection>;
+}
+
+/// 已认证的辅助进程连接
+pub struct Connection {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Connection {
+    pub fn new(reader: impl BufRead + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+        }
+    }
+
+    fn request(&mut self, line: &str) -> std::io::Result<Result<(), String>> {
+        protocol::request(&mut self.reader, &mut self.writer, line)
+    }
+}
+
+/// 通过以管理员权限运行的辅助进程关闭连接
+///
+/// 第一次关闭连接时才启动辅助进程; 辅助进程退出后下次关闭连接时重新启动。
+/// 启动失败(包括用户拒绝 UAC 授权)后不再尝试, 转为降级模式, 需要以管理员身份重新启动本程序
+pub struct HelperClient<L> {
+    launcher: L,
+    state: Mutex<ClientState>,
+}
+
+#[derive(Default)]
+struct ClientState {
+    connection: Option<Connection>,
+    /// 启动失败的原因
+    launch_error: Option<String>,
+}
+
+impl<L: Launcher> HelperClient<L> {
+    pub fn new(launcher: L) -> Self {
+        HelperClient {
+            launcher,
+            state: Mutex::default(),
+        }
+    }
+
+    fn connection<'a>(&self, state: &'a mut ClientState) -> Result<&'a mut Connection> {
+        if let Some(e) = &state.launch_error {
+            anyhow::bail!("无法启动管理员权限辅助进程: {}", e);
+        }
+        if state.connection.is_none() {
+            match self.launcher.launch() {
+                Ok(connection) => state.connection = Some(connection),
+                Err(e) => {
+                    error!("无法启动管理员权限辅助进程: {}", e);
+                    state.launch_error = Some(e.to_string());
+                    return Err(e);
+                }
+            }
+        }
+        state
+            .connection
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("辅助进程未连接"))
+    }
+}
+
+impl<L: Launcher> ConnectionCloser for HelperClient<L> {
+    fn name(&self) -> &'static str {
+        "helper"
+    }
+
+    fn is_available(&self) -> bool {
+        self.state
+            .lock()
+            .is_ok_and(|state| state.launch_error.is_none())
+    }
+
+    fn close(&self, info: &NetworkInfo) -> Result<()> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取辅助进程连接锁: {}", e))?;
+        let line = protocol::close_request(info);
+        let reply = match self.connection(&mut state)?.request(&line) {
+            Ok(reply) => reply,
+            Err(e) => {
+                // 辅助进程已退出, 重新启动一次后重试
+                warn!("辅助进程已断开连接: {}, 正在重新启动", e);
+                state.connection = None;
+                self.connection(&mut state)?.request(&line)?
+            }
+        };
+        reply.map_err(|e| anyhow::anyhow!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 依次返回给定的回复, 用完后启动失败
+    struct FakeLauncher {
+        replies: Mutex<Vec<&'static str>>,
+        launches: AtomicUsize,
+    }
+
+    impl FakeLauncher {
+        fn new(replies: &[&'static str]) -> Self {
+            FakeLauncher {
+                replies: Mutex::new(replies.iter().rev().copied().collect()),
+                launches: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Launcher for &FakeLauncher {
+        fn launch(&self) -> Result<Connection> {
+            self.launches.fetch_add(1, Ordering::SeqCst);
+            match self.replies.lock().unwrap().pop() {
+                Some(reply) => Ok(Connection::new(Cursor::new(reply), std::io::sink())),
+                None => anyhow::bail!("用户拒绝了 UAC 授权"),
+            }
+        }
+    }
+
+    fn info() -> NetworkInfo {
+        NetworkInfo {
+            local_addr: 1,
+            local_port: 2,
+            remote_addr: 3,
+            remote_port: 4,
+        }
+    }
+
+    #[test]
+    fn launch_failure_makes_client_unavailable() {
+        let launcher = FakeLauncher::new(&[]);
+        let client = HelperClient::new(&launcher);
+        assert!(client.is_available());
+        assert!(client.close(&info()).is_err());
+        assert!(!client.is_available());
+        // 不再弹出 UAC 授权窗口
+        assert!(client.close(&info()).is_err());
+        assert_eq!(launcher.launches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn relaunches_after_helper_exits() {
+        let launcher = FakeLauncher::new(&["ok\n", "err 不是炉石的连接\n"]);
+        let client = HelperClient::new(&launcher);
+        client.close(&info()).unwrap();
+        // 第一个辅助进程的连接已读完, 视为已退出
+        let e = client.close(&info()).unwrap_err();
+        assert_eq!(e.to_string(), "不是炉石的连接");
+        assert_eq!(launcher.launches.load(Ordering::SeqCst), 2);
+        assert!(client.is_available());
+    }
+
+    #[test]
+    fn relaunch_failure_makes_client_unavailable() {
+        let launcher = FakeLauncher::new(&["ok\n"]);
+        let client = HelperClient::new(&launcher);
+        client.close(&info()).unwrap();
+        assert!(client.close(&info()).is_err());
+        assert!(!client.is_available());
+    }
+}
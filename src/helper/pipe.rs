@@ -0,0 +1,126 @@
+use anyhow::Result;
+use log::info;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use windows::Win32::Foundation::{ERROR_PIPE_CONNECTED, HANDLE};
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, GetNamedPipeClientProcessId, PIPE_READMODE_BYTE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+};
+use windows::Win32::System::Threading::{GetProcessId, WaitForSingleObject};
+use windows::core::HSTRING;
+
+use super::client::{Connection, Launcher};
+use super::protocol;
+use crate::token;
+
+/// 用户同意 UAC 授权后等待辅助进程连接管道的最长时间
+const HELPER_START_TIMEOUT: Duration = Duration::from_secs(10);
+const PIPE_BUFFER_SIZE: u32 = 4096;
+
+/// 通过命名管道连接以管理员权限启动的辅助进程
+pub struct PipeLauncher;
+
+impl Launcher for PipeLauncher {
+    /// 创建管道, 以管理员权限启动辅助进程并等待其连接和认证
+    ///
+    /// 管道只有一个实例且不接受远程连接, 连接后校验对方的进程 ID 和令牌
+    fn launch(&self) -> Result<Connection> {
+        let token = token::generate(32)?;
+        let name = format!(
+            r"\\.\pipe\hsarec-helper-{}-{}",
+            std::process::id(),
+            token::generate(8)?
+        );
+        let pipe = create_pipe(&name)?;
+        let args = [
+            "helper".into(),
+            "--pipe".into(),
+            name.clone().into(),
+            "--token".into(),
+            token.clone().into(),
+        ];
+        let process = super::spawn_elevated(&args, true)?;
+        let helper_pid = unsafe { GetProcessId(HANDLE(process.as_raw_handle())) };
+        wait_for_client(&pipe, &name, process)?;
+
+        let mut client_pid = 0;
+        unsafe { GetNamedPipeClientProcessId(HANDLE(pipe.as_raw_handle()), &mut client_pid) }?;
+        anyhow::ensure!(
+            client_pid == helper_pid,
+            "辅助进程没有在 {} 秒内连接",
+            HELPER_START_TIMEOUT.as_secs()
+        );
+        let mut writer = pipe;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        protocol::authenticate(&mut reader, &mut writer, &token)?;
+        info!("已连接管理员权限辅助进程, 进程 ID {}", helper_pid);
+        Ok(Connection::new(reader, writer))
+    }
+}
+
+fn create_pipe(name: &str) -> Result<File> {
+    let handle = unsafe {
+        CreateNamedPipeW(
+            &HSTRING::from(name),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            None,
+        )
+    };
+    anyhow::ensure!(
+        !handle.is_invalid(),
+        "无法创建命名管道: {}",
+        std::io::Error::last_os_error()
+    );
+    Ok(unsafe { File::from_raw_handle(handle.0) })
+}
+
+/// 等待客户端连接管道
+///
+/// 辅助进程退出或超时后由另一个线程自己连接管道来结束等待, 调用方通过进程 ID 区分
+fn wait_for_client(pipe: &File, name: &str, process: OwnedHandle) -> Result<()> {
+    let connected = Arc::new(AtomicBool::new(false));
+    {
+        let connected = connected.clone();
+        let name = name.to_string();
+        std::thread::spawn(move || {
+            unsafe {
+                WaitForSingleObject(
+                    HANDLE(process.as_raw_handle()),
+                    HELPER_START_TIMEOUT.as_millis() as u32,
+                )
+            };
+            if !connected.load(Ordering::SeqCst) {
+                let _ = std::fs::OpenOptions::new().read(true).write(true).open(&name);
+            }
+        });
+    }
+    let result = unsafe { ConnectNamedPipe(HANDLE(pipe.as_raw_handle()), None) };
+    connected.store(true, Ordering::SeqCst);
+    match result {
+        Err(e) if e.code() != ERROR_PIPE_CONNECTED.to_hresult() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 辅助进程: 连接界面进程创建的管道并认证, 之后处理请求直到管道断开
+pub fn run(pipe_name: &str, token: &str) -> Result<()> {
+    let mut writer = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(pipe_name)?;
+    let mut reader = BufReader::new(writer.try_clone()?);
+    protocol::request(&mut reader, &mut writer, &format!("auth {}", token))?
+        .map_err(|e| anyhow::anyhow!("界面进程拒绝连接: {}", e))?;
+    protocol::serve(reader, writer)
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use std::io::{BufRead, Write};
+
+use crate::hearthstone;
+use crate::network::{self, NetworkInfo};
+use crate::token;
+
+/// 界面进程与辅助进程之间的逐行文本协议
+///
+/// 辅助进程连接后先发送 `auth <令牌>`, 之后界面进程每行发送一个请求
+/// `close <本地地址> <本地端口> <远程地址> <远程端口>`(均为 Windows API 中的原始 u32),
+/// 对方回复 `ok` 或 `err <原因>`
+///
+/// 外层错误表示连接已断开, 内层错误为对方拒绝请求的原因
+pub fn request(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    line: &str,
+) -> std::io::Result<Result<(), String>> {
+    writeln!(writer, "{}", line)?;
+    writer.flush()?;
+    let mut reply = String::new();
+    if reader.read_line(&mut reply)? == 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(match reply.trim_end() {
+        "ok" => Ok(()),
+        reply => Err(reply.strip_prefix("err ").unwrap_or(reply).to_string()),
+    })
+}
+
+/// 读取并校验辅助进程的认证消息, 失败时回复错误
+pub fn authenticate(reader: &mut impl BufRead, writer: &mut impl Write, token: &str) -> Result<()> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let authenticated = line
+        .trim_end()
+        .strip_prefix("auth ")
+        .is_some_and(|actual| token::matches(token, actual));
+    if !authenticated {
+        let _ = writeln!(writer, "err 认证失败");
+        anyhow::bail!("辅助进程认证失败");
+    }
+    writeln!(writer, "ok")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// 处理请求直到界面进程断开连接
+pub fn serve(reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let reply = match handle(&line) {
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("err {}", e.to_string().replace('\n', " ")),
+        };
+        writeln!(writer, "{}", reply)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// 只允许关闭炉石进程的连接
+fn handle(line: &str) -> Result<()> {
+    let args = line
+        .strip_prefix("close ")
+        .ok_or_else(|| anyhow::anyhow!("未知请求: {}", line))?;
+    let info = parse_close(args).ok_or_else(|| anyhow::anyhow!("无效的请求参数: {}", args))?;
+    anyhow::ensure!(
+        hearthstone::get_connections()?.contains(&info),
+        "{} 不是炉石的连接",
+        info
+    );
+    network::close_tcp_connection(&info)
+}
+
+/// 生成 `close` 请求
+pub fn close_request(info: &NetworkInfo) -> String {
+    format!(
+        "close {} {} {} {}",
+        info.local_addr, info.local_port, info.remote_addr, info.remote_port
+    )
+}
+
+/// 解析 `close` 请求的参数
+fn parse_close(args: &str) -> Option<NetworkInfo> {
+    let mut fields = args.split(' ').map(|field| field.parse::<u32>());
+    let info = NetworkInfo {
+        local_addr: fields.next()?.ok()?,
+        local_port: fields.next()?.ok()?,
+        remote_addr: fields.next()?.ok()?,
+        remote_port: fields.next()?.ok()?,
+    };
+    fields.next().is_none().then_some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn authenticate_checks_token() {
+        let mut reply = Vec::new();
+        authenticate(&mut Cursor::new("auth abc\n"), &mut reply, "abc").unwrap();
+        assert_eq!(reply, b"ok\n");
+
+        let mut reply = Vec::new();
+        assert!(authenticate(&mut Cursor::new("auth abd\n"), &mut reply, "abc").is_err());
+        assert_eq!(reply, "err 认证失败\n".as_bytes());
+        assert!(authenticate(&mut Cursor::new(""), &mut Vec::new(), "abc").is_err());
+    }
+
+    #[test]
+    fn request_separates_refusal_from_disconnect() {
+        let mut sent = Vec::new();
+        let reply = request(&mut Cursor::new("err 不是炉石的连接\n"), &mut sent, "close 1 2 3 4");
+        assert_eq!(reply.unwrap(), Err("不是炉石的连接".to_string()));
+        assert_eq!(sent, b"close 1 2 3 4\n");
+        assert!(request(&mut Cursor::new(""), &mut Vec::new(), "close 1 2 3 4").is_err());
+    }
+
+    #[test]
+    fn serve_rejects_malformed_requests() {
+        let mut replies = Vec::new();
+        serve(Cursor::new("auth x\nclose 1 2 3\nclose 1 2 3 4 5\n"), &mut replies).unwrap();
+        assert_eq!(
+            String::from_utf8(replies).unwrap(),
+            "err 未知请求: auth x\nerr 无效的请求参数: 1 2 3\nerr 无效的请求参数: 1 2 3 4 5\n"
+        );
+    }
+
+    #[test]
+    fn close_request_round_trips() {
+        let info = NetworkInfo {
+            local_addr: 16777343,
+            local_port: 1,
+            remote_addr: u32::MAX,
+            remote_port: 65535,
+        };
+        let line = close_request(&info);
+        assert_eq!(parse_close(line.strip_prefix("close ").unwrap()), Some(info));
+    }
+}
@@ -1,142 +1,1163 @@
 use flume::Sender;
-use inputbot::KeybdKey;
-use log::warn;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::focus::FocusGuard;
 use crate::tray::TrayMessage;
 
-// Helper functions for hotkey parsing
-pub fn key_string_to_keybdkey(key_str: &str) -> Option<KeybdKey> {
-    match key_str.to_uppercase().as_str() {
-        "F1" => Some(KeybdKey::F1Key),
-        "F2" => Some(KeybdKey::F2Key),
-        "F3" => Some(KeybdKey::F3Key),
-        "F4" => Some(KeybdKey::F4Key),
-        "F5" => Some(KeybdKey::F5Key),
-        "F6" => Some(KeybdKey::F6Key),
-        "F7" => Some(KeybdKey::F7Key),
-        "F8" => Some(KeybdKey::F8Key),
-        "F9" => Some(KeybdKey::F9Key),
-        "F10" => Some(KeybdKey::F10Key),
-        "F11" => Some(KeybdKey::F11Key),
-        "F12" => Some(KeybdKey::F12Key),
-        "CTRL" => Some(KeybdKey::LControlKey), // Default to Left Control
-        "LCTRL" | "LCONTROL" => Some(KeybdKey::LControlKey),
-        "RCTRL" | "RCONTROL" => Some(KeybdKey::RControlKey),
-        "SHIFT" => Some(KeybdKey::LShiftKey), // Default to Left Shift
-        "LSHIFT" => Some(KeybdKey::LShiftKey),
-        "RSHIFT" => Some(KeybdKey::RShiftKey),
-        "ALT" => Some(KeybdKey::LAltKey), // Default to Left Alt
-        "LALT" => Some(KeybdKey::LAltKey),
-        "RALT" => Some(KeybdKey::RAltKey),
-        "A" => Some(KeybdKey::AKey),
-        "B" => Some(KeybdKey::BKey),
-        "C" => Some(KeybdKey::CKey),
-        "D" => Some(KeybdKey::DKey),
-        "E" => Some(KeybdKey::EKey),
-        "F" => Some(KeybdKey::FKey),
-        "G" => Some(KeybdKey::GKey),
-        "H" => Some(KeybdKey::HKey),
-        "I" => Some(KeybdKey::IKey),
-        "J" => Some(KeybdKey::JKey),
-        "K" => Some(KeybdKey::KKey),
-        "L" => Some(KeybdKey::LKey),
-        "M" => Some(KeybdKey::MKey),
-        "N" => Some(KeybdKey::NKey),
-        "O" => Some(KeybdKey::OKey),
-        "P" => Some(KeybdKey::PKey),
-        "Q" => Some(KeybdKey::QKey),
-        "R" => Some(KeybdKey::RKey),
-        "S" => Some(KeybdKey::SKey),
-        "T" => Some(KeybdKey::TKey),
-        "U" => Some(KeybdKey::UKey),
-        "V" => Some(KeybdKey::VKey),
-        "W" => Some(KeybdKey::WKey),
-        "X" => Some(KeybdKey::XKey),
-        "Y" => Some(KeybdKey::YKey),
-        "Z" => Some(KeybdKey::ZKey),
-        "0" | "NUM0" => Some(KeybdKey::Numrow0Key),
-        "1" | "NUM1" => Some(KeybdKey::Numrow1Key),
-        "2" | "NUM2" => Some(KeybdKey::Numrow2Key),
-        "3" | "NUM3" => Some(KeybdKey::Numrow3Key),
-        "4" | "NUM4" => Some(KeybdKey::Numrow4Key),
-        "5" | "NUM5" => Some(KeybdKey::Numrow5Key),
-        "6" | "NUM6" => Some(KeybdKey::Numrow6Key),
-        "7" | "NUM7" => Some(KeybdKey::Numrow7Key),
-        "8" | "NUM8" => Some(KeybdKey::Numrow8Key),
-        "9" | "NUM9" => Some(KeybdKey::Numrow9Key),
-        "NUMPAD0" => Some(KeybdKey::Numpad0Key),
-        "NUMPAD1" => Some(KeybdKey::Numpad1Key),
-        "NUMPAD2" => Some(KeybdKey::Numpad2Key),
-        "NUMPAD3" => Some(KeybdKey::Numpad3Key),
-        "NUMPAD4" => Some(KeybdKey::Numpad4Key),
-        "NUMPAD5" => Some(KeybdKey::Numpad5Key),
-        "NUMPAD6" => Some(KeybdKey::Numpad6Key),
-        "NUMPAD7" => Some(KeybdKey::Numpad7Key),
-        "NUMPAD8" => Some(KeybdKey::Numpad8Key),
-        "NUMPAD9" => Some(KeybdKey::Numpad9Key),
-        "ESC" | "ESCAPE" => Some(KeybdKey::EscapeKey),
-        "SPACE" => Some(KeybdKey::SpaceKey),
-        "ENTER" => Some(KeybdKey::EnterKey),
-        "TAB" => Some(KeybdKey::TabKey),
-        _ => {
-            warn!("警告: 未映射的键字符串: {}", key_str);
-            None
-        }
-    }
-}
-
-pub fn parse_hotkey_config(hotkey_str: &str) -> (Option<KeybdKey>, Vec<KeybdKey>) {
-    let parts: Vec<String> = hotkey_str
-        .split('+')
-        .map(|s| s.trim().to_uppercase())
-        .collect();
-    if parts.is_empty() {
-        warn!("警告: 热键字符串为空。");
-        return (None, Vec::new());
-    }
-
-    // The last part is considered the main key, others are modifiers
-    let main_key_str = parts.last().unwrap();
-    let main_key = key_string_to_keybdkey(main_key_str);
-    if main_key.is_none() {
-        warn!(
-            "警告: 无法解析主键: '{}' 从配置 '{}'",
-            main_key_str, hotkey_str
-        );
+#[cfg(target_os = "linux")]
+mod evdev;
+#[cfg(test)]
+pub mod fake;
+#[cfg(windows)]
+mod inputbot;
+
+/// 修饰键, 不带左右前缀的表示任意一侧
+///
+/// 变体顺序即显示顺序, 例如 `Ctrl+Shift+Alt+Win+R`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Modifier {
+    Ctrl,
+    LCtrl,
+    RCtrl,
+    Shift,
+    LShift,
+    RShift,
+    Alt,
+    LAlt,
+    RAlt,
+    Win,
+    LWin,
+    RWin,
+}
+
+/// 全部区分左右的物理修饰键
+const SIDED_MODIFIERS: [Modifier; 8] = [
+    Modifier::LCtrl,
+    Modifier::RCtrl,
+    Modifier::LShift,
+    Modifier::RShift,
+    Modifier::LAlt,
+    Modifier::RAlt,
+    Modifier::LWin,
+    Modifier::RWin,
+];
+
+impl Modifier {
+    /// 该修饰键对应的物理按键, 不区分左右的修饰键对应两侧
+    pub fn sides(self) -> &'static [Modifier] {
+        match self {
+            Modifier::Ctrl => &[Modifier::LCtrl, Modifier::RCtrl],
+            Modifier::Shift => &[Modifier::LShift, Modifier::RShift],
+            Modifier::Alt => &[Modifier::LAlt, Modifier::RAlt],
+            Modifier::Win => &[Modifier::LWin, Modifier::RWin],
+            Modifier::LCtrl => &[Modifier::LCtrl],
+            Modifier::RCtrl => &[Modifier::RCtrl],
+            Modifier::LShift => &[Modifier::LShift],
+            Modifier::RShift => &[Modifier::RShift],
+            Modifier::LAlt => &[Modifier::LAlt],
+            Modifier::RAlt => &[Modifier::RAlt],
+            Modifier::LWin => &[Modifier::LWin],
+            Modifier::RWin => &[Modifier::RWin],
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "CTRL" | "CONTROL" => Some(Modifier::Ctrl),
+            "LCTRL" | "LCONTROL" => Some(Modifier::LCtrl),
+            "RCTRL" | "RCONTROL" => Some(Modifier::RCtrl),
+            "SHIFT" => Some(Modifier::Shift),
+            "LSHIFT" => Some(Modifier::LShift),
+            "RSHIFT" => Some(Modifier::RShift),
+            "ALT" => Some(Modifier::Alt),
+            "LALT" => Some(Modifier::LAlt),
+            "RALT" | "ALTGR" => Some(Modifier::RAlt),
+            "WIN" | "META" | "SUPER" | "CMD" => Some(Modifier::Win),
+            "LWIN" | "LMETA" | "LSUPER" => Some(Modifier::LWin),
+            "RWIN" | "RMETA" | "RSUPER" => Some(Modifier::RWin),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Modifier::Ctrl => "Ctrl",
+            Modifier::LCtrl => "LCtrl",
+            Modifier::RCtrl => "RCtrl",
+            Modifier::Shift => "Shift",
+            Modifier::LShift => "LShift",
+            Modifier::RShift => "RShift",
+            Modifier::Alt => "Alt",
+            Modifier::LAlt => "LAlt",
+            Modifier::RAlt => "RAlt",
+            Modifier::Win => "Win",
+            Modifier::LWin => "LWin",
+            Modifier::RWin => "RWin",
+        };
+        f.write_str(name)
     }
+}
+
+/// 热键的主键, 与具体的输入库无关
+///
+/// 标点键统一用名称表示(如 `Comma`、`Minus`), 避免与分隔符 `+`、`,` 冲突
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// 字母键 A-Z(大写)
+    Letter(char),
+    /// 主键盘数字行 0-9
+    Digit(u8),
+    /// 小键盘数字 0-9
+    Numpad(u8),
+    /// 小键盘上的运算符和回车, 与主键盘上的同名键区分
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
+    NumpadEqual,
+    /// F1-F24
+    F(u8),
+    Space,
+    Enter,
+    Tab,
+    Escape,
+    Backspace,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    Backquote,
+    Minus,
+    Equal,
+    LBracket,
+    RBracket,
+    Backslash,
+    Semicolon,
+    Quote,
+    Comma,
+    Period,
+    Slash,
+    /// ISO 键盘左 Shift 旁边的额外按键
+    IntlBackslash,
+    MouseMiddle,
+    /// 鼠标侧键(后退)
+    MouseX1,
+    /// 鼠标侧键(前进)
+    MouseX2,
+}
+
+impl Key {
+    /// 按名称解析主键, 不区分大小写
+    ///
+    /// 除 `Display` 输出的名称外只接受以下别名: `Num0`-`Num9`(主键盘数字, 兼容旧配置)、
+    /// `Return`、`Ins`、`Del`、`PgUp`、`PgDn`、`Grave`、`MButton`、`XButton1`/`MouseBack`、
+    /// `XButton2`/`MouseForward`, 以及标点键本身的字符(`,` 和 `+` 是分隔符, 不能直接使用)。
+    /// `Plus` 与 `Equal` 不是同一个键在所有键盘布局上的名称, 因此不作为别名
+    fn parse(name: &str) -> Option<Self> {
+        let upper = name.to_uppercase();
+        let mut chars = upper.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            match c {
+                'A'..='Z' => return Some(Key::Letter(c)),
+                '0'..='9' => return Some(Key::Digit(c as u8 - b'0')),
+                _ => {}
+            }
+        }
+        let number = |prefix: &str, max: u8| {
+            upper
+                .strip_prefix(prefix)
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|n| *n <= max)
+        };
+        if let Some(n) = number("NUMPAD", 9) {
+            return Some(Key::Numpad(n));
+        }
+        if let Some(n) = number("NUM", 9) {
+            return Some(Key::Digit(n));
+        }
+        if let Some(n) = number("F", 24).filter(|n| *n >= 1) {
+            return Some(Key::F(n));
+        }
+        let key = match upper.as_str() {
+            "SPACE" => Key::Space,
+            "ENTER" | "RETURN" => Key::Enter,
+            "TAB" => Key::Tab,
+            "ESC" | "ESCAPE" => Key::Escape,
+            "BACKSPACE" => Key::Backspace,
+            "INSERT" | "INS" => Key::Insert,
+            "DELETE" | "DEL" => Key::Delete,
+            "HOME" => Key::Home,
+            "END" => Key::End,
+            "PAGEUP" | "PGUP" => Key::PageUp,
+            "PAGEDOWN" | "PGDN" => Key::PageDown,
+            "UP" => Key::Up,
+            "DOWN" => Key::Down,
+            "LEFT" => Key::Left,
+            "RIGHT" => Key::Right,
+            "`" | "BACKQUOTE" | "GRAVE" => Key::Backquote,
+            "-" | "MINUS" => Key::Minus,
+            "=" | "EQUAL" => Key::Equal,
+            "[" | "LBRACKET" => Key::LBracket,
+            "]" | "RBRACKET" => Key::RBracket,
+            "\\" | "BACKSLASH" => Key::Backslash,
+            ";" | "SEMICOLON" => Key::Semicolon,
+            "'" | "QUOTE" => Key::Quote,
+            "COMMA" => Key::Comma,
+            "." | "PERIOD" => Key::Period,
+            "/" | "SLASH" => Key::Slash,
+            "NUMPADADD" => Key::NumpadAdd,
+            "NUMPADSUBTRACT" => Key::NumpadSubtract,
+            "NUMPADMULTIPLY" => Key::NumpadMultiply,
+            "NUMPADDIVIDE" => Key::NumpadDivide,
+            "NUMPADDECIMAL" => Key::NumpadDecimal,
+            "NUMPADENTER" => Key::NumpadEnter,
+            "NUMPADEQUAL" => Key::NumpadEqual,
+            "INTLBACKSLASH" => Key::IntlBackslash,
+            "MOUSEMIDDLE" | "MBUTTON" => Key::MouseMiddle,
+            "MOUSEX1" | "XBUTTON1" | "MOUSEBACK" => Key::MouseX1,
+            "MOUSEX2" | "XBUTTON2" | "MOUSEFORWARD" => Key::MouseX2,
+            _ => return None,
+        };
+        Some(key)
+    }
+}
 
-    let mut modifier_keys = Vec::new();
-    if parts.len() > 1 {
-        for i in 0..parts.len() - 1 {
-            match key_string_to_keybdkey(&parts[i]) {
-                Some(key) => {
-                    modifier_keys.push(key);
+impl Key {
+    /// 全部主键, 用于在按键序列进行到一半时监听其他按键
+    fn all() -> impl Iterator<Item = Key> {
+        const NAMED: [Key; 37] = [
+            Key::Space,
+            Key::Enter,
+            Key::Tab,
+            Key::Escape,
+            Key::Backspace,
+            Key::Insert,
+            Key::Delete,
+            Key::Home,
+            Key::End,
+            Key::PageUp,
+            Key::PageDown,
+            Key::Up,
+            Key::Down,
+            Key::Left,
+            Key::Right,
+            Key::Backquote,
+            Key::Minus,
+            Key::Equal,
+            Key::LBracket,
+            Key::RBracket,
+            Key::Backslash,
+            Key::Semicolon,
+            Key::Quote,
+            Key::Comma,
+            Key::Period,
+            Key::Slash,
+            Key::NumpadAdd,
+            Key::NumpadSubtract,
+            Key::NumpadMultiply,
+            Key::NumpadDivide,
+            Key::NumpadDecimal,
+            Key::NumpadEnter,
+            Key::NumpadEqual,
+            Key::IntlBackslash,
+            Key::MouseMiddle,
+            Key::MouseX1,
+            Key::MouseX2,
+        ];
+        ('A'..='Z')
+            .map(Key::Letter)
+            .chain((0..=9).map(Key::Digit))
+            .chain((0..=9).map(Key::Numpad))
+            .chain((1..=24).map(Key::F))
+            .chain(NAMED)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Key::Letter(c) => return write!(f, "{}", c),
+            Key::Digit(n) => return write!(f, "{}", n),
+            Key::Numpad(n) => return write!(f, "Numpad{}", n),
+            Key::F(n) => return write!(f, "F{}", n),
+            Key::Space => "Space",
+            Key::Enter => "Enter",
+            Key::Tab => "Tab",
+            Key::Escape => "Esc",
+            Key::Backspace => "Backspace",
+            Key::Insert => "Insert",
+            Key::Delete => "Delete",
+            Key::Home => "Home",
+            Key::End => "End",
+            Key::PageUp => "PageUp",
+            Key::PageDown => "PageDown",
+            Key::Up => "Up",
+            Key::Down => "Down",
+            Key::Left => "Left",
+            Key::Right => "Right",
+            Key::Backquote => "Backquote",
+            Key::Minus => "Minus",
+            Key::Equal => "Equal",
+            Key::LBracket => "LBracket",
+            Key::RBracket => "RBracket",
+            Key::Backslash => "Backslash",
+            Key::Semicolon => "Semicolon",
+            Key::Quote => "Quote",
+            Key::Comma => "Comma",
+            Key::Period => "Period",
+            Key::Slash => "Slash",
+            Key::NumpadAdd => "NumpadAdd",
+            Key::NumpadSubtract => "NumpadSubtract",
+            Key::NumpadMultiply => "NumpadMultiply",
+            Key::NumpadDivide => "NumpadDivide",
+            Key::NumpadDecimal => "NumpadDecimal",
+            Key::NumpadEnter => "NumpadEnter",
+            Key::NumpadEqual => "NumpadEqual",
+            Key::IntlBackslash => "IntlBackslash",
+            Key::MouseMiddle => "MouseMiddle",
+            Key::MouseX1 => "MouseX1",
+            Key::MouseX2 => "MouseX2",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 热键解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyParseError {
+    Empty,
+    /// 存在空的片段, 例如 `Ctrl++R` 或结尾多余的 `+`
+    EmptyPart,
+    UnknownKey(String),
+    DuplicateModifier(Modifier),
+    MultipleKeys(Key, Key),
+    /// 只有修饰键, 没有主键
+    MissingKey,
+    /// 按键序列中存在空的步骤, 例如 `Ctrl+K,,D`
+    EmptyStep,
+}
+
+impl fmt::Display for HotkeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyParseError::Empty => write!(f, "热键不能为空"),
+            HotkeyParseError::EmptyPart => write!(f, "热键中存在空的按键, 请检查多余的 '+'"),
+            HotkeyParseError::UnknownKey(name) => write!(f, "无法识别的按键: {}", name),
+            HotkeyParseError::DuplicateModifier(modifier) => {
+                write!(f, "修饰键重复: {}", modifier)
+            }
+            HotkeyParseError::MultipleKeys(first, second) => {
+                write!(f, "热键只能有一个主键: {} 和 {}", first, second)
+            }
+            HotkeyParseError::MissingKey => write!(f, "热键缺少主键, 不能只有修饰键"),
+            HotkeyParseError::EmptyStep => {
+                write!(f, "按键序列中存在空的步骤, 请检查多余的 ','")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HotkeyParseError {}
+
+/// 一个热键组合: 若干修饰键 + 一个主键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    /// 已排序且不重复
+    pub modifiers: Vec<Modifier>,
+    pub key: Key,
+}
+
+impl FromStr for Hotkey {
+    type Err = HotkeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(HotkeyParseError::Empty);
+        }
+
+        let mut modifiers = Vec::new();
+        let mut key = None;
+        for part in s.split('+').map(str::trim) {
+            if part.is_empty() {
+                return Err(HotkeyParseError::EmptyPart);
+            }
+            if let Some(modifier) = Modifier::parse(part) {
+                if modifiers.contains(&modifier) {
+                    return Err(HotkeyParseError::DuplicateModifier(modifier));
                 }
-                None => {}
+                modifiers.push(modifier);
+                continue;
             }
+            let parsed =
+                Key::parse(part).ok_or_else(|| HotkeyParseError::UnknownKey(part.to_string()))?;
+            if let Some(existing) = key {
+                return Err(HotkeyParseError::MultipleKeys(existing, parsed));
+            }
+            key = Some(parsed);
         }
+
+        modifiers.sort();
+        Ok(Hotkey {
+            modifiers,
+            key: key.ok_or(HotkeyParseError::MissingKey)?,
+        })
     }
-    (main_key, modifier_keys)
 }
 
-pub fn register_hotkey(tx: Sender<TrayMessage>, main_key: KeybdKey, modifier_keys: Vec<KeybdKey>) {
-    let modifier_keys_clone = modifier_keys.clone();
-    main_key.bind(move || {
-        let mut all_modifiers_pressed = true;
-        if !modifier_keys_clone.is_empty() {
-            for modifier in &modifier_keys_clone {
-                if !modifier.is_pressed() {
-                    all_modifiers_pressed = false;
-                    break;
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl Hotkey {
+    /// 严格匹配修饰键时, 是否存在某个按键状态能同时触发两个热键
+    ///
+    /// 例如 `Ctrl+R` 与 `LCtrl+R` 重叠, `LCtrl+R` 与 `RCtrl+R` 不重叠
+    pub fn overlaps(&self, other: &Hotkey) -> bool {
+        self.key == other.key
+            && (0..1u32 << SIDED_MODIFIERS.len()).any(|mask| {
+                let state = PressedMask(mask);
+                modifiers_match(&self.modifiers, &state, true)
+                    && modifiers_match(&other.modifiers, &state, true)
+            })
+    }
+}
+
+/// 用位掩码表示按下了哪些物理修饰键, 位序与 `SIDED_MODIFIERS` 一致
+struct PressedMask(u32);
+
+impl KeyState for PressedMask {
+    fn is_pressed(&self, modifier: Modifier) -> bool {
+        SIDED_MODIFIERS
+            .iter()
+            .position(|side| *side == modifier)
+            .is_some_and(|i| self.0 & (1 << i) != 0)
+    }
+}
+
+/// 按键序列(组合键), 例如 `Ctrl+K, D`: 依次按下每一步, 相邻两步的间隔不能超过超时时间
+///
+/// 只有一步时与普通热键相同
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HotkeySequence(pub Vec<Hotkey>);
+
+impl FromStr for HotkeySequence {
+    type Err = HotkeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(HotkeyParseError::Empty);
+        }
+        s.split(',')
+            .map(|step| {
+                if step.trim().is_empty() {
+                    Err(HotkeyParseError::EmptyStep)
+                } else {
+                    step.parse::<Hotkey>()
                 }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(HotkeySequence)
+    }
+}
+
+impl fmt::Display for HotkeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
             }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// 查询物理修饰键(`LCtrl`、`RShift` 等区分左右的变体)当前是否按下
+pub trait KeyState {
+    fn is_pressed(&self, modifier: Modifier) -> bool;
+}
+
+/// 热键的底层实现: 监听主键按下并回报修饰键状态
+///
+/// 同一个主键只保留一个回调, 重复注册时覆盖之前的回调
+pub trait HotkeyBackend: KeyState + Send + Sync {
+    fn name(&self) -> &'static str;
+    /// `key` 按下时调用 `callback`, 当前平台不支持该按键时返回错误
+    fn register(&self, key: Key, callback: Callback) -> anyhow::Result<()>;
+    fn unregister(&self, key: Key);
+    /// 阻塞运行输入事件循环
+    fn run(&self);
+}
+
+pub type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// 根据当前平台选择热键实现
+pub fn default_backend() -> Arc<dyn HotkeyBackend> {
+    #[cfg(windows)]
+    {
+        Arc::new(inputbot::InputbotBackend)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Arc::new(evdev::EvdevBackend::new())
+    }
+}
+
+/// 判断当前修饰键状态是否满足 `required`
+///
+/// 不区分左右的修饰键按下任意一侧即可; `strict` 为真时, 其余未要求的修饰键必须全部松开,
+/// 避免 `Shift+Alt+R` 在按下 `Ctrl+Shift+Alt+R` 时也被触发
+pub fn modifiers_match(
+    required: &[Modifier],
+    state: &(impl KeyState + ?Sized),
+    strict: bool,
+) -> bool {
+    let all_pressed = required
+        .iter()
+        .all(|modifier| modifier.sides().iter().any(|side| state.is_pressed(*side)));
+    if !all_pressed || !strict {
+        return all_pressed;
+    }
+    SIDED_MODIFIERS
+        .iter()
+        .filter(|side| {
+            !required
+                .iter()
+                .any(|modifier| modifier.sides().contains(side))
+        })
+        .all(|side| !state.is_pressed(*side))
+}
+
+/// 热键可以触发的动作
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum HotkeyAction {
+    /// 断开日志中记录的游戏服务器连接
+    Disconnect,
+    /// 断开炉石的全部TCP连接
+    DisconnectAll,
+    OpenSettings,
+    /// 弹出当前状态提示
+    ShowStatus,
+    /// 弱网模拟
+    Degrade,
+}
+
+impl HotkeyAction {
+    /// 全部动作, 顺序与设置窗口中的动作下拉框一致
+    pub const ALL: [HotkeyAction; 5] = [
+        HotkeyAction::Disconnect,
+        HotkeyAction::DisconnectAll,
+        HotkeyAction::OpenSettings,
+        HotkeyAction::ShowStatus,
+        HotkeyAction::Degrade,
+    ];
+
+    pub fn message(self) -> TrayMessage {
+        match self {
+            HotkeyAction::Disconnect => TrayMessage::Reconnect,
+            HotkeyAction::DisconnectAll => TrayMessage::ReconnectAll,
+            HotkeyAction::OpenSettings => TrayMessage::Setting,
+            HotkeyAction::ShowStatus => TrayMessage::ShowStatus,
+            HotkeyAction::Degrade => TrayMessage::Degrade,
         }
-        if all_modifiers_pressed {
-           let _ = tx.send(TrayMessage::Reconnect);
+    }
+
+    /// 用于提示信息的动作名称
+    pub fn label(self) -> &'static str {
+        match self {
+            HotkeyAction::Disconnect => "开始拔线",
+            HotkeyAction::DisconnectAll => "断开全部连接",
+            HotkeyAction::OpenSettings => "打开设置",
+            HotkeyAction::ShowStatus => "显示状态",
+            HotkeyAction::Degrade => "弱网模拟",
         }
-    });
+    }
 }
 
-pub fn unregister_hotkey(main_key: KeybdKey) {
-    main_key.unbind();
+/// 炉石(以及游戏时常用的系统快捷键)默认占用的按键及其用途
+///
+/// 热键不会拦截按键, 这些按键在触发热键的同时也会被游戏收到
+const GAME_BINDINGS: [(&str, &str); 8] = [
+    ("Escape", "打开游戏菜单"),
+    ("Enter", "打开聊天"),
+    ("R", "酒馆战棋: 刷新酒馆"),
+    ("F", "酒馆战棋: 冻结酒馆"),
+    ("Alt+Enter", "切换全屏"),
+    ("Alt+F4", "退出游戏"),
+    ("Alt+Tab", "切换窗口"),
+    ("Win+D", "显示桌面"),
+];
+
+/// 热键冲突
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyConflict {
+    /// 与炉石的默认按键重叠
+    Game { hotkey: Hotkey, usage: &'static str },
+    /// 与本程序已绑定到其他动作的热键重叠, 或者一方是另一方的前缀
+    Binding {
+        sequence: HotkeySequence,
+        action: HotkeyAction,
+    },
+}
+
+impl fmt::Display for HotkeyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyConflict::Game { hotkey, usage } => {
+                write!(f, "与炉石的 {}({}) 冲突", hotkey, usage)
+            }
+            HotkeyConflict::Binding { sequence, action } => {
+                write!(f, "与「{}」的热键 {} 冲突", action.label(), sequence)
+            }
+        }
+    }
+}
+
+/// 检查将 `sequence` 绑定到 `action` 时的冲突
+///
+/// `hotkeys` 中已绑定到同一动作的热键会被替换, 不算冲突
+pub fn find_conflicts(
+    sequence: &HotkeySequence,
+    action: HotkeyAction,
+    hotkeys: &BTreeMap<String, HotkeyAction>,
+) -> Vec<HotkeyConflict> {
+    let mut conflicts = Vec::new();
+    for (combo, usage) in GAME_BINDINGS {
+        let Ok(game) = combo.parse::<Hotkey>() else {
+            continue;
+        };
+        if sequence.0.iter().any(|step| step.overlaps(&game)) {
+            conflicts.push(HotkeyConflict::Game {
+                hotkey: game,
+                usage,
+            });
+        }
+    }
+
+    for (combo, bound) in hotkeys {
+        if *bound == action {
+            continue;
+        }
+        let Ok(other) = combo.parse::<HotkeySequence>() else {
+            continue;
+        };
+        // 较短的序列先完成匹配, 较长的那个永远不会被触发
+        let prefix = sequence.0.iter().zip(&other.0).all(|(a, b)| a.overlaps(b));
+        if prefix {
+            conflicts.push(HotkeyConflict::Binding {
+                sequence: other,
+                action: *bound,
+            });
+        }
+    }
+    conflicts
+}
+
+/// 按键序列的匹配状态机, 记录当前已经完成的步骤
+pub struct ChordMatcher {
+    bindings: Vec<(HotkeySequence, TrayMessage)>,
+    strict: bool,
+    timeout: Duration,
+    progress: Vec<Hotkey>,
+    last_step: Option<Instant>,
+}
+
+impl ChordMatcher {
+    pub fn new(
+        bindings: Vec<(HotkeySequence, TrayMessage)>,
+        strict: bool,
+        timeout: Duration,
+    ) -> Self {
+        ChordMatcher {
+            bindings,
+            strict,
+            timeout,
+            progress: Vec::new(),
+            last_step: None,
+        }
+    }
+
+    /// 处理一次主键按下, 某个序列完整匹配时返回其消息
+    pub fn on_key(
+        &mut self,
+        key: Key,
+        state: &(impl KeyState + ?Sized),
+        now: Instant,
+    ) -> Option<TrayMessage> {
+        if self
+            .last_step
+            .is_some_and(|last| now.duration_since(last) > self.timeout)
+        {
+            self.reset();
+        }
+
+        if let Some(message) = self.advance(key, state, now) {
+            return Some(message);
+        }
+        // 当前进度下没有可以继续的序列, 从头开始重新匹配这一步
+        if !self.progress.is_empty() {
+            self.reset();
+            return self.advance(key, state, now);
+        }
+        None
+    }
+
+    fn advance(
+        &mut self,
+        key: Key,
+        state: &(impl KeyState + ?Sized),
+        now: Instant,
+    ) -> Option<TrayMessage> {
+        let depth = self.progress.len();
+        let (sequence, message) = self
+            .bindings
+            .iter()
+            .filter(|(sequence, _)| {
+                sequence.0.len() > depth && sequence.0[..depth] == self.progress[..]
+            })
+            .filter(|(sequence, _)| {
+                let step = &sequence.0[depth];
+                step.key == key && modifiers_match(&step.modifiers, state, self.strict)
+            })
+            // 优先完成已经完整的序列, 其次选择修饰键更多(更具体)的一步
+            .max_by_key(|(sequence, _)| {
+                (
+                    sequence.0.len() == depth + 1,
+                    sequence.0[depth].modifiers.len(),
+                )
+            })?;
+
+        if sequence.0.len() == depth + 1 {
+            let message = message.clone();
+            self.reset();
+            return Some(message);
+        }
+        self.progress.push(sequence.0[depth].clone());
+        self.last_step = Some(now);
+        None
+    }
+
+    fn reset(&mut self) {
+        self.progress.clear();
+        self.last_step = None;
+    }
+}
+
+/// 注册全部热键, 返回已绑定的主键列表, 供配置变化时统一注销
+///
+/// 每个主键只能绑定一个回调, 因此所有序列共用一个匹配状态机, 每个用到的主键都绑定到它;
+/// 提供了 `focus_guard` 时, 只有炉石在前台才会发出热键动作
+pub fn register_hotkeys(
+    backend: &Arc<dyn HotkeyBackend>,
+    tx: Sender<TrayMessage>,
+    hotkeys: &BTreeMap<String, HotkeyAction>,
+    strict: bool,
+    chord_timeout: Duration,
+    focus_guard: Option<Arc<FocusGuard>>,
+) -> Vec<Key> {
+    let mut bindings = Vec::new();
+    let mut keys = HashSet::new();
+    for (combo, action) in hotkeys {
+        match combo.parse::<HotkeySequence>() {
+            Ok(sequence) => {
+                info!("注册热键 {} -> {:?}", sequence, action);
+                for conflict in find_conflicts(&sequence, *action, hotkeys) {
+                    warn!("警告: 热键 {} {}。", sequence, conflict);
+                }
+                keys.extend(sequence.0.iter().map(|step| step.key));
+                bindings.push((sequence, action.message()));
+            }
+            Err(e) => warn!(
+                "警告: 无法解析热键 '{}': {}。将不会注册该热键({:?})。",
+                combo, e, action
+            ),
+        }
+    }
+
+    // 按键序列进行到一半时按下其他键需要重新开始匹配, 因此存在多步序列时监听全部按键
+    let listened: HashSet<Key> = if bindings.iter().any(|(sequence, _)| sequence.0.len() > 1) {
+        keys.iter().copied().chain(Key::all()).collect()
+    } else {
+        keys.clone()
+    };
+    let matcher = Arc::new(Mutex::new(ChordMatcher::new(
+        bindings,
+        strict,
+        chord_timeout,
+    )));
+    let mut registered = Vec::new();
+    for key in listened {
+        let tx = tx.clone();
+        let matcher = Arc::clone(&matcher);
+        let state = Arc::clone(backend);
+        let focus_guard = focus_guard.clone();
+        let callback: Callback = Arc::new(move || {
+            let message = match matcher.lock() {
+                Ok(mut matcher) => matcher.on_key(key, state.as_ref(), Instant::now()),
+                Err(e) => {
+                    warn!("无法获取热键状态锁: {}", e);
+                    None
+                }
+            };
+            let Some(message) = message else {
+                return;
+            };
+            if let Some(guard) = &focus_guard
+                && !guard.is_game_focused()
+            {
+                info!("炉石不在前台, 忽略热键动作 {:?}", message);
+                return;
+            }
+            let _ = tx.send(message);
+        });
+        match backend.register(key, callback) {
+            Ok(_) => registered.push(key),
+            // 只用于打断按键序列的按键, 当前平台不支持时忽略
+            Err(_) if !keys.contains(&key) => {}
+            Err(e) => warn!(
+                "警告: [{}] 无法注册按键 {}: {}。包含该按键的热键不会生效。",
+                backend.name(),
+                key,
+                e
+            ),
+        }
+    }
+    registered
+}
+
+pub fn unregister_hotkeys(backend: &Arc<dyn HotkeyBackend>, keys: &[Key]) {
+    for key in keys {
+        backend.unregister(*key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkey(s: &str) -> Hotkey {
+        s.parse().unwrap()
+    }
+
+    /// 只按下指定物理修饰键的按键状态
+    struct Held(Vec<Modifier>);
+
+    impl KeyState for Held {
+        fn is_pressed(&self, modifier: Modifier) -> bool {
+            self.0.contains(&modifier)
+        }
+    }
+
+    fn held(modifiers: &[Modifier]) -> Held {
+        Held(modifiers.to_vec())
+    }
+
+    #[test]
+    fn hotkey_round_trips_through_display() {
+        let keys = [
+            "A", "Z", "0", "9", "Numpad0", "Numpad9", "F1", "F24", "Space", "Enter", "Tab",
+            "Esc", "Backspace", "Insert", "Delete", "Home", "End", "PageUp", "PageDown", "Up",
+            "Down", "Left", "Right", "Backquote", "Minus", "Equal", "LBracket", "RBracket",
+            "Backslash", "Semicolon", "Quote", "Comma", "Period", "Slash", "NumpadAdd",
+            "NumpadSubtract", "NumpadMultiply", "NumpadDivide", "NumpadDecimal", "NumpadEnter",
+            "NumpadEqual", "IntlBackslash", "MouseMiddle", "MouseX1", "MouseX2",
+        ];
+        for key in keys {
+            for combo in [key.to_string(), format!("LCtrl+RShift+Alt+Win+{}", key)] {
+                let parsed = hotkey(&combo);
+                assert_eq!(parsed.to_string(), combo);
+                assert_eq!(hotkey(&parsed.to_string()), parsed);
+            }
+        }
+    }
+
+    #[test]
+    fn hotkey_display_is_canonical() {
+        assert_eq!(hotkey(" alt + shift + r ").to_string(), "Shift+Alt+R");
+        assert_eq!(hotkey("control+escape").to_string(), "Ctrl+Esc");
+        assert_eq!(hotkey("Num5").to_string(), "5");
+        assert_eq!(hotkey("AltGr+=").to_string(), "RAlt+Equal");
+    }
+
+    #[test]
+    fn hotkey_rejects_invalid_input() {
+        let error = |s: &str| s.parse::<Hotkey>().unwrap_err();
+        assert_eq!(error(" "), HotkeyParseError::Empty);
+        assert_eq!(error("Ctrl++R"), HotkeyParseError::EmptyPart);
+        assert_eq!(error("Ctrl+R+"), HotkeyParseError::EmptyPart);
+        assert_eq!(error("Ctrl+Plus"), HotkeyParseError::UnknownKey("Plus".to_string()));
+        assert_eq!(error("F25"), HotkeyParseError::UnknownKey("F25".to_string()));
+        assert_eq!(error("Numpad10"), HotkeyParseError::UnknownKey("Numpad10".to_string()));
+        assert_eq!(error("Ctrl+Control+R"), HotkeyParseError::DuplicateModifier(Modifier::Ctrl));
+        assert_eq!(
+            error("R+T"),
+            HotkeyParseError::MultipleKeys(Key::Letter('R'), Key::Letter('T'))
+        );
+        assert_eq!(error("Ctrl+Shift"), HotkeyParseError::MissingKey);
+    }
+
+    #[test]
+    fn sequence_round_trips_through_display() {
+        let sequence: HotkeySequence = "ctrl+k,d".parse().unwrap();
+        assert_eq!(sequence.to_string(), "Ctrl+K, D");
+        assert_eq!(sequence.to_string().parse::<HotkeySequence>().unwrap(), sequence);
+        assert_eq!(
+            "Ctrl+K,,D".parse::<HotkeySequence>().unwrap_err(),
+            HotkeyParseError::EmptyStep
+        );
+        assert_eq!("".parse::<HotkeySequence>().unwrap_err(), HotkeyParseError::Empty);
+    }
+
+    #[test]
+    fn modifiers_match_exactly_when_strict() {
+        let required = hotkey("Shift+Alt+R").modifiers;
+        let exact = held(&[Modifier::LShift, Modifier::RAlt]);
+        let extra = held(&[Modifier::LShift, Modifier::LAlt, Modifier::RCtrl]);
+        let missing = held(&[Modifier::LShift]);
+        assert!(modifiers_match(&required, &exact, true));
+        assert!(modifiers_match(&required, &exact, false));
+        assert!(!modifiers_match(&required, &extra, true));
+        assert!(modifiers_match(&required, &extra, false));
+        assert!(!modifiers_match(&required, &missing, true));
+        assert!(!modifiers_match(&required, &missing, false));
+        // 没有修饰键的热键在严格模式下要求所有修饰键都松开
+        assert!(modifiers_match(&[], &held(&[]), true));
+        assert!(!modifiers_match(&[], &held(&[Modifier::LWin]), true));
+    }
+
+    #[test]
+    fn sided_modifiers_match_only_their_side() {
+        let generic = hotkey("Ctrl+R").modifiers;
+        let left = hotkey("LCtrl+R").modifiers;
+        let lctrl = held(&[Modifier::LCtrl]);
+        let rctrl = held(&[Modifier::RCtrl]);
+        assert!(modifiers_match(&generic, &lctrl, true));
+        assert!(modifiers_match(&generic, &rctrl, true));
+        assert!(modifiers_match(&left, &lctrl, true));
+        assert!(!modifiers_match(&left, &rctrl, true));
+        assert!(!modifiers_match(&left, &rctrl, false));
+        // 两侧同时按下: 通用修饰键满足, 只要求左侧时右侧算作多余的修饰键
+        let both = held(&[Modifier::LCtrl, Modifier::RCtrl]);
+        assert!(modifiers_match(&generic, &both, true));
+        assert!(!modifiers_match(&left, &both, true));
+        assert!(modifiers_match(&left, &both, false));
+    }
+
+    fn matcher(bindings: &[(&str, TrayMessage)]) -> ChordMatcher {
+        let bindings = bindings
+            .iter()
+            .map(|(combo, message)| (combo.parse().unwrap(), message.clone()))
+            .collect();
+        ChordMatcher::new(bindings, true, Duration::from_secs(1))
+    }
+
+    #[test]
+    fn chord_matcher_completes_sequences() {
+        let mut matcher = matcher(&[
+            ("Ctrl+K, D", TrayMessage::Reconnect),
+            ("Ctrl+K, Ctrl+D", TrayMessage::ReconnectAll),
+            ("D", TrayMessage::Setting),
+        ]);
+        let ctrl = held(&[Modifier::LCtrl]);
+        let none = held(&[]);
+        let now = Instant::now();
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, now).is_none());
+        assert!(matches!(
+            matcher.on_key(Key::Letter('D'), &none, now),
+            Some(TrayMessage::Reconnect)
+        ));
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, now).is_none());
+        assert!(matches!(
+            matcher.on_key(Key::Letter('D'), &ctrl, now),
+            Some(TrayMessage::ReconnectAll)
+        ));
+        // 没有进行中的序列时, 单步热键照常触发
+        assert!(matches!(
+            matcher.on_key(Key::Letter('D'), &none, now),
+            Some(TrayMessage::Setting)
+        ));
+    }
+
+    #[test]
+    fn chord_matcher_resets_on_other_keys() {
+        let mut matcher = matcher(&[
+            ("Ctrl+K, D", TrayMessage::Reconnect),
+            ("Ctrl+J", TrayMessage::Setting),
+        ]);
+        let ctrl = held(&[Modifier::LCtrl]);
+        let none = held(&[]);
+        let now = Instant::now();
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, now).is_none());
+        assert!(matcher.on_key(Key::Letter('X'), &none, now).is_none());
+        assert!(matcher.on_key(Key::Letter('D'), &none, now).is_none());
+        // 序列中间按下的键本身可以开始新的匹配
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, now).is_none());
+        assert!(matches!(
+            matcher.on_key(Key::Letter('J'), &ctrl, now),
+            Some(TrayMessage::Setting)
+        ));
+        assert!(matcher.on_key(Key::Letter('D'), &none, now).is_none());
+    }
+
+    #[test]
+    fn chord_matcher_resets_after_timeout() {
+        let mut matcher = matcher(&[("Ctrl+K, D", TrayMessage::Reconnect)]);
+        let ctrl = held(&[Modifier::LCtrl]);
+        let none = held(&[]);
+        let start = Instant::now();
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, start).is_none());
+        let late = start + Duration::from_millis(1500);
+        assert!(matcher.on_key(Key::Letter('D'), &none, late).is_none());
+        assert!(matcher.on_key(Key::Letter('K'), &ctrl, late).is_none());
+        assert!(matches!(
+            matcher.on_key(Key::Letter('D'), &none, late + Duration::from_millis(900)),
+            Some(TrayMessage::Reconnect)
+        ));
+    }
+
+    #[test]
+    fn all_keys_round_trip() {
+        let keys: Vec<Key> = Key::all().collect();
+        assert_eq!(keys.len(), keys.iter().collect::<HashSet<_>>().len());
+        for key in keys {
+            assert_eq!(Key::parse(&key.to_string()), Some(key));
+        }
+    }
+
+    #[test]
+    fn registers_and_dispatches_through_backend() {
+        let fake = Arc::new(fake::FakeBackend::new());
+        let backend: Arc<dyn HotkeyBackend> = fake.clone();
+        let (tx, rx) = flume::unbounded();
+        let hotkeys = BTreeMap::from([
+            ("Shift+Alt+R".to_string(), HotkeyAction::Disconnect),
+            ("NumpadSubtract".to_string(), HotkeyAction::Degrade),
+        ]);
+        let keys = register_hotkeys(&backend, tx, &hotkeys, true, Duration::from_secs(1), None);
+        assert!(fake.is_registered(Key::Letter('R')));
+        assert!(fake.is_registered(Key::NumpadSubtract));
+        // 没有多步序列时只监听绑定的主键
+        assert!(!fake.is_registered(Key::Minus));
+        assert!(!fake.press(Key::Minus));
+
+        fake.hold(Modifier::LShift);
+        fake.hold(Modifier::RAlt);
+        assert!(fake.press(Key::Letter('R')));
+        assert!(matches!(rx.try_recv(), Ok(TrayMessage::Reconnect)));
+        fake.hold(Modifier::LCtrl);
+        assert!(fake.press(Key::Letter('R')));
+        assert!(rx.try_recv().is_err());
+        fake.release(Modifier::LCtrl);
+        fake.release(Modifier::LShift);
+        fake.release(Modifier::RAlt);
+        assert!(fake.press(Key::NumpadSubtract));
+        assert!(matches!(rx.try_recv(), Ok(TrayMessage::Degrade)));
+
+        unregister_hotkeys(&backend, &keys);
+        assert!(!fake.is_registered(Key::Letter('R')));
+        assert!(!fake.press(Key::NumpadSubtract));
+    }
+
+    #[test]
+    fn sequences_listen_to_all_keys() {
+        let fake = Arc::new(fake::FakeBackend::new());
+        let backend: Arc<dyn HotkeyBackend> = fake.clone();
+        let (tx, rx) = flume::unbounded();
+        let hotkeys = BTreeMap::from([("Ctrl+K, D".to_string(), HotkeyAction::Disconnect)]);
+        let keys = register_hotkeys(&backend, tx, &hotkeys, true, Duration::from_secs(1), None);
+        assert!(fake.is_registered(Key::Letter('X')));
+
+        fake.hold(Modifier::LCtrl);
+        fake.press(Key::Letter('K'));
+        fake.release(Modifier::LCtrl);
+        fake.press(Key::Letter('X'));
+        fake.press(Key::Letter('D'));
+        assert!(rx.try_recv().is_err());
+
+        fake.hold(Modifier::RCtrl);
+        fake.press(Key::Letter('K'));
+        fake.release(Modifier::RCtrl);
+        fake.press(Key::Letter('D'));
+        assert!(matches!(rx.try_recv(), Ok(TrayMessage::Reconnect)));
+
+        unregister_hotkeys(&backend, &keys);
+        assert!(!fake.is_registered(Key::Letter('X')));
+    }
+
+    #[test]
+    fn overlapping_hotkeys() {
+        assert!(hotkey("Ctrl+R").overlaps(&hotkey("LCtrl+R")));
+        assert!(hotkey("Ctrl+R").overlaps(&hotkey("Control+R")));
+        assert!(!hotkey("LCtrl+R").overlaps(&hotkey("RCtrl+R")));
+        assert!(!hotkey("Ctrl+R").overlaps(&hotkey("Ctrl+T")));
+        // 严格匹配时多按一个修饰键就是不同的热键
+        assert!(!hotkey("Ctrl+R").overlaps(&hotkey("Ctrl+Shift+R")));
+        assert!(!hotkey("R").overlaps(&hotkey("Shift+R")));
+    }
+
+    #[test]
+    fn conflicts_with_game_bindings() {
+        let hotkeys = BTreeMap::new();
+        let conflicts = |combo: &str| {
+            find_conflicts(&combo.parse().unwrap(), HotkeyAction::Disconnect, &hotkeys)
+        };
+        assert!(matches!(
+            conflicts("LAlt+F4")[..],
+            [HotkeyConflict::Game { usage: "退出游戏", .. }]
+        ));
+        assert!(matches!(
+            conflicts("Ctrl+K, Esc")[..],
+            [HotkeyConflict::Game { usage: "打开游戏菜单", .. }]
+        ));
+        assert!(!conflicts("R").is_empty());
+        assert!(conflicts("Shift+Alt+R").is_empty());
+        assert!(conflicts("Ctrl+F4").is_empty());
+    }
+
+    #[test]
+    fn conflicts_with_other_actions() {
+        let hotkeys = BTreeMap::from([
+            ("Shift+Alt+R".to_string(), HotkeyAction::Disconnect),
+            ("Ctrl+K, D".to_string(), HotkeyAction::DisconnectAll),
+        ]);
+        let conflicts = |combo: &str, action| find_conflicts(&combo.parse().unwrap(), action, &hotkeys);
+        assert!(matches!(
+            conflicts("Shift+LAlt+R", HotkeyAction::OpenSettings)[..],
+            [HotkeyConflict::Binding { action: HotkeyAction::Disconnect, .. }]
+        ));
+        // 重新绑定同一个动作时替换原来的热键, 不算冲突
+        assert!(conflicts("Shift+LAlt+R", HotkeyAction::Disconnect).is_empty());
+        // 一方是另一方的前缀
+        assert!(matches!(
+            conflicts("Ctrl+K", HotkeyAction::ShowStatus)[..],
+            [HotkeyConflict::Binding { action: HotkeyAction::DisconnectAll, .. }]
+        ));
+        assert!(matches!(
+            conflicts("Ctrl+K, D, X", HotkeyAction::ShowStatus)[..],
+            [HotkeyConflict::Binding { action: HotkeyAction::DisconnectAll, .. }]
+        ));
+        assert!(conflicts("Ctrl+K, X", HotkeyAction::ShowStatus).is_empty());
+    }
 }
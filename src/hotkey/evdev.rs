@@ -0,0 +1,330 @@
+use ::evdev::{Device, EventSummary, KeyCode};
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Callback, HotkeyBackend, Key, KeyState, Modifier};
+
+/// evdev 按键码与修饰键的对应关系
+const MODIFIER_CODES: [(KeyCode, Modifier); 8] = [
+    (KeyCode::KEY_LEFTCTRL, Modifier::LCtrl),
+    (KeyCode::KEY_RIGHTCTRL, Modifier::RCtrl),
+    (KeyCode::KEY_LEFTSHIFT, Modifier::LShift),
+    (KeyCode::KEY_RIGHTSHIFT, Modifier::RShift),
+    (KeyCode::KEY_LEFTALT, Modifier::LAlt),
+    (KeyCode::KEY_RIGHTALT, Modifier::RAlt),
+    (KeyCode::KEY_LEFTMETA, Modifier::LWin),
+    (KeyCode::KEY_RIGHTMETA, Modifier::RWin),
+];
+
+/// evdev 按键码与主键的对应关系
+const KEY_CODES: &[(KeyCode, Key)] = &[
+    (KeyCode::KEY_A, Key::Letter('A')),
+    (KeyCode::KEY_B, Key::Letter('B')),
+    (KeyCode::KEY_C, Key::Letter('C')),
+    (KeyCode::KEY_D, Key::Letter('D')),
+    (KeyCode::KEY_E, Key::Letter('E')),
+    (KeyCode::KEY_F, Key::Letter('F')),
+    (KeyCode::KEY_G, Key::Letter('G')),
+    (KeyCode::KEY_H, Key::Letter('H')),
+    (KeyCode::KEY_I, Key::Letter('I')),
+    (KeyCode::KEY_J, Key::Letter('J')),
+    (KeyCode::KEY_K, Key::Letter('K')),
+    (KeyCode::KEY_L, Key::Letter('L')),
+    (KeyCode::KEY_M, Key::Letter('M')),
+    (KeyCode::KEY_N, Key::Letter('N')),
+    (KeyCode::KEY_O, Key::Letter('O')),
+    (KeyCode::KEY_P, Key::Letter('P')),
+    (KeyCode::KEY_Q, Key::Letter('Q')),
+    (KeyCode::KEY_R, Key::Letter('R')),
+    (KeyCode::KEY_S, Key::Letter('S')),
+    (KeyCode::KEY_T, Key::Letter('T')),
+    (KeyCode::KEY_U, Key::Letter('U')),
+    (KeyCode::KEY_V, Key::Letter('V')),
+    (KeyCode::KEY_W, Key::Letter('W')),
+    (KeyCode::KEY_X, Key::Letter('X')),
+    (KeyCode::KEY_Y, Key::Letter('Y')),
+    (KeyCode::KEY_Z, Key::Letter('Z')),
+    (KeyCode::KEY_0, Key::Digit(0)),
+    (KeyCode::KEY_1, Key::Digit(1)),
+    (KeyCode::KEY_2, Key::Digit(2)),
+    (KeyCode::KEY_3, Key::Digit(3)),
+    (KeyCode::KEY_4, Key::Digit(4)),
+    (KeyCode::KEY_5, Key::Digit(5)),
+    (KeyCode::KEY_6, Key::Digit(6)),
+    (KeyCode::KEY_7, Key::Digit(7)),
+    (KeyCode::KEY_8, Key::Digit(8)),
+    (KeyCode::KEY_9, Key::Digit(9)),
+    (KeyCode::KEY_KP0, Key::Numpad(0)),
+    (KeyCode::KEY_KP1, Key::Numpad(1)),
+    (KeyCode::KEY_KP2, Key::Numpad(2)),
+    (KeyCode::KEY_KP3, Key::Numpad(3)),
+    (KeyCode::KEY_KP4, Key::Numpad(4)),
+    (KeyCode::KEY_KP5, Key::Numpad(5)),
+    (KeyCode::KEY_KP6, Key::Numpad(6)),
+    (KeyCode::KEY_KP7, Key::Numpad(7)),
+    (KeyCode::KEY_KP8, Key::Numpad(8)),
+    (KeyCode::KEY_KP9, Key::Numpad(9)),
+    (KeyCode::KEY_F1, Key::F(1)),
+    (KeyCode::KEY_F2, Key::F(2)),
+    (KeyCode::KEY_F3, Key::F(3)),
+    (KeyCode::KEY_F4, Key::F(4)),
+    (KeyCode::KEY_F5, Key::F(5)),
+    (KeyCode::KEY_F6, Key::F(6)),
+    (KeyCode::KEY_F7, Key::F(7)),
+    (KeyCode::KEY_F8, Key::F(8)),
+    (KeyCode::KEY_F9, Key::F(9)),
+    (KeyCode::KEY_F10, Key::F(10)),
+    (KeyCode::KEY_F11, Key::F(11)),
+    (KeyCode::KEY_F12, Key::F(12)),
+    (KeyCode::KEY_F13, Key::F(13)),
+    (KeyCode::KEY_F14, Key::F(14)),
+    (KeyCode::KEY_F15, Key::F(15)),
+    (KeyCode::KEY_F16, Key::F(16)),
+    (KeyCode::KEY_F17, Key::F(17)),
+    (KeyCode::KEY_F18, Key::F(18)),
+    (KeyCode::KEY_F19, Key::F(19)),
+    (KeyCode::KEY_F20, Key::F(20)),
+    (KeyCode::KEY_F21, Key::F(21)),
+    (KeyCode::KEY_F22, Key::F(22)),
+    (KeyCode::KEY_F23, Key::F(23)),
+    (KeyCode::KEY_F24, Key::F(24)),
+    (KeyCode::KEY_SPACE, Key::Space),
+    (KeyCode::KEY_ENTER, Key::Enter),
+    (KeyCode::KEY_TAB, Key::Tab),
+    (KeyCode::KEY_ESC, Key::Escape),
+    (KeyCode::KEY_BACKSPACE, Key::Backspace),
+    (KeyCode::KEY_INSERT, Key::Insert),
+    (KeyCode::KEY_DELETE, Key::Delete),
+    (KeyCode::KEY_HOME, Key::Home),
+    (KeyCode::KEY_END, Key::End),
+    (KeyCode::KEY_PAGEUP, Key::PageUp),
+    (KeyCode::KEY_PAGEDOWN, Key::PageDown),
+    (KeyCode::KEY_UP, Key::Up),
+    (KeyCode::KEY_DOWN, Key::Down),
+    (KeyCode::KEY_LEFT, Key::Left),
+    (KeyCode::KEY_RIGHT, Key::Right),
+    (KeyCode::KEY_GRAVE, Key::Backquote),
+    (KeyCode::KEY_MINUS, Key::Minus),
+    (KeyCode::KEY_EQUAL, Key::Equal),
+    (KeyCode::KEY_LEFTBRACE, Key::LBracket),
+    (KeyCode::KEY_RIGHTBRACE, Key::RBracket),
+    (KeyCode::KEY_BACKSLASH, Key::Backslash),
+    (KeyCode::KEY_SEMICOLON, Key::Semicolon),
+    (KeyCode::KEY_APOSTROPHE, Key::Quote),
+    (KeyCode::KEY_COMMA, Key::Comma),
+    (KeyCode::KEY_DOT, Key::Period),
+    (KeyCode::KEY_SLASH, Key::Slash),
+    (KeyCode::BTN_MIDDLE, Key::MouseMiddle),
+    (KeyCode::BTN_SIDE, Key::MouseX1),
+    (KeyCode::BTN_EXTRA, Key::MouseX2),
+    (KeyCode::KEY_KPPLUS, Key::NumpadAdd),
+    (KeyCode::KEY_KPMINUS, Key::NumpadSubtract),
+    (KeyCode::KEY_KPASTERISK, Key::NumpadMultiply),
+    (KeyCode::KEY_KPSLASH, Key::NumpadDivide),
+    (KeyCode::KEY_KPDOT, Key::NumpadDecimal),
+    (KeyCode::KEY_KPENTER, Key::NumpadEnter),
+    (KeyCode::KEY_KPEQUAL, Key::NumpadEqual),
+    (KeyCode::KEY_102ND, Key::IntlBackslash),
+];
+
+/// 输入设备所在目录
+const INPUT_DIR: &str = "/dev/input";
+/// 重新扫描输入设备的间隔, 用于发现新插入的键盘和鼠标
+const RESCAN_INTERVAL: Duration = Duration::from_secs(3);
+
+/// evdev 按键事件的值
+const KEY_RELEASED: i32 = 0;
+const KEY_PRESSED: i32 = 1;
+
+/// 基于 evdev 的 Linux 实现, 直接读取 `/dev/input/event*`, 不依赖 X11/Wayland
+///
+/// 需要当前用户有输入设备的读取权限(通常是加入 `input` 组)
+pub struct EvdevBackend {
+    callbacks: Mutex<HashMap<Key, Callback>>,
+    pressed: Mutex<HashSet<Modifier>>,
+}
+
+impl EvdevBackend {
+    pub fn new() -> Self {
+        EvdevBackend {
+            callbacks: Mutex::new(HashMap::new()),
+            pressed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 是否是可以产生热键的设备(键盘或带侧键的鼠标)
+    fn is_input_device(device: &Device) -> bool {
+        device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(KeyCode::KEY_A) || keys.contains(KeyCode::BTN_SIDE))
+    }
+
+    /// 打开尚未监听的输入设备并交给 `spawn`, 返回是否有正在监听的设备
+    fn open_new_devices(
+        listening: &Mutex<HashSet<PathBuf>>,
+        mut spawn: impl FnMut(PathBuf, Device),
+    ) -> bool {
+        let Ok(mut listening_paths) = listening.lock() else {
+            return false;
+        };
+        let entries = match std::fs::read_dir(INPUT_DIR) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("无法读取 {}: {}", INPUT_DIR, e);
+                return !listening_paths.is_empty();
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_event = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"));
+            if !is_event || listening_paths.contains(&path) {
+                continue;
+            }
+            // 没有权限或不是键盘/鼠标的设备在下次扫描时再尝试
+            let Ok(device) = Device::open(&path) else {
+                continue;
+            };
+            if Self::is_input_device(&device) {
+                listening_paths.insert(path.clone());
+                spawn(path, device);
+            }
+        }
+        !listening_paths.is_empty()
+    }
+
+    /// 持续读取一个输入设备, 设备被拔出或读取失败时返回, 下次扫描时会重新打开
+    fn listen(&self, path: &Path, mut device: Device) {
+        info!(
+            "正在监听输入设备 {:?} ({})",
+            path,
+            device.name().unwrap_or("未知设备")
+        );
+        loop {
+            match device.fetch_events() {
+                Ok(events) => {
+                    for event in events {
+                        if let EventSummary::Key(_, code, value) = event.destructure() {
+                            self.handle(code, value);
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("停止读取输入设备 {:?}: {}", path, e);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle(&self, code: KeyCode, value: i32) {
+        if let Some((_, modifier)) = MODIFIER_CODES.iter().find(|(c, _)| *c == code) {
+            if let Ok(mut pressed) = self.pressed.lock() {
+                match value {
+                    KEY_PRESSED => {
+                        pressed.insert(*modifier);
+                    }
+                    KEY_RELEASED => {
+                        pressed.remove(modifier);
+                    }
+                    // 长按重复不改变状态
+                    _ => {}
+                }
+            }
+            return;
+        }
+
+        if value != KEY_PRESSED {
+            return;
+        }
+        let Some((_, key)) = KEY_CODES.iter().find(|(c, _)| *c == code) else {
+            return;
+        };
+        // 在锁外调用回调, 避免回调中再次注册热键时死锁
+        let callback = self
+            .callbacks
+            .lock()
+            .ok()
+            .and_then(|callbacks| callbacks.get(key).cloned());
+        if let Some(callback) = callback {
+            callback();
+        }
+    }
+}
+
+impl KeyState for EvdevBackend {
+    fn is_pressed(&self, modifier: Modifier) -> bool {
+        match self.pressed.lock() {
+            Ok(pressed) => modifier.sides().iter().any(|side| pressed.contains(side)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl HotkeyBackend for EvdevBackend {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn register(&self, key: Key, callback: Callback) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            KEY_CODES.iter().any(|(_, k)| *k == key),
+            "evdev 不支持按键 {}",
+            key
+        );
+        self.callbacks
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取热键回调锁: {}", e))?
+            .insert(key, callback);
+        Ok(())
+    }
+
+    fn unregister(&self, key: Key) {
+        if let Ok(mut callbacks) = self.callbacks.lock() {
+            callbacks.remove(&key);
+        }
+    }
+
+    fn run(&self) {
+        let listening = Mutex::new(HashSet::new());
+        let mut warned = false;
+        std::thread::scope(|scope| {
+            loop {
+                let found = Self::open_new_devices(&listening, |path, device| {
+                    let listening = &listening;
+                    scope.spawn(move || {
+                        self.listen(&path, device);
+                        if let Ok(mut listening) = listening.lock() {
+                            listening.remove(&path);
+                        }
+                    });
+                });
+                if !found && !warned {
+                    warn!(
+                        "没有找到可读取的输入设备, 热键暂时不会生效。请确认当前用户有 /dev/input 的读取权限。"
+                    );
+                    warned = true;
+                }
+                std::thread::sleep(RESCAN_INTERVAL);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_key_has_one_code() {
+        for (_, key) in KEY_CODES {
+            assert_eq!(KEY_CODES.iter().filter(|(_, k)| k == key).count(), 1, "{}", key);
+        }
+        assert!(KEY_CODES.contains(&(KeyCode::KEY_KPMINUS, Key::NumpadSubtract)));
+    }
+}
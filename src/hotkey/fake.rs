@@ -0,0 +1,66 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::{Callback, HotkeyBackend, Key, KeyState, Modifier};
+
+/// 不读取真实输入设备的实现, 由调用方模拟按键, 用于测试热键逻辑
+#[derive(Default)]
+pub struct FakeBackend {
+    callbacks: Mutex<HashMap<Key, Callback>>,
+    pressed: Mutex<HashSet<Modifier>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按住物理修饰键(应使用区分左右的变体)
+    pub fn hold(&self, modifier: Modifier) {
+        self.pressed.lock().unwrap().insert(modifier);
+    }
+
+    pub fn release(&self, modifier: Modifier) {
+        self.pressed.lock().unwrap().remove(&modifier);
+    }
+
+    /// 模拟按下主键, 返回是否有回调被调用
+    pub fn press(&self, key: Key) -> bool {
+        let callback = self.callbacks.lock().unwrap().get(&key).cloned();
+        match callback {
+            Some(callback) => {
+                callback();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_registered(&self, key: Key) -> bool {
+        self.callbacks.lock().unwrap().contains_key(&key)
+    }
+}
+
+impl KeyState for FakeBackend {
+    fn is_pressed(&self, modifier: Modifier) -> bool {
+        let pressed = self.pressed.lock().unwrap();
+        modifier.sides().iter().any(|side| pressed.contains(side))
+    }
+}
+
+impl HotkeyBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn register(&self, key: Key, callback: Callback) -> anyhow::Result<()> {
+        self.callbacks.lock().unwrap().insert(key, callback);
+        Ok(())
+    }
+
+    fn unregister(&self, key: Key) {
+        self.callbacks.lock().unwrap().remove(&key);
+    }
+
+    fn run(&self) {}
+}
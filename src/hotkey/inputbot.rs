@@ -0,0 +1,167 @@
+use ::inputbot::{KeybdKey, MouseButton};
+
+use super::{Callback, HotkeyBackend, Key, KeyState, Modifier};
+
+/// 基于 inputbot 的实现(Windows 低级键盘/鼠标钩子)
+pub struct InputbotBackend;
+
+impl KeyState for InputbotBackend {
+    fn is_pressed(&self, modifier: Modifier) -> bool {
+        let key = match modifier {
+            Modifier::LCtrl => KeybdKey::LControlKey,
+            Modifier::RCtrl => KeybdKey::RControlKey,
+            Modifier::LShift => KeybdKey::LShiftKey,
+            Modifier::RShift => KeybdKey::RShiftKey,
+            Modifier::LAlt => KeybdKey::LAltKey,
+            Modifier::RAlt => KeybdKey::RAltKey,
+            Modifier::LWin => KeybdKey::LSuper,
+            Modifier::RWin => KeybdKey::RSuper,
+            // 不区分左右的修饰键按任意一侧处理
+            generic => return generic.sides().iter().any(|side| self.is_pressed(*side)),
+        };
+        key.is_pressed()
+    }
+}
+
+impl HotkeyBackend for InputbotBackend {
+    fn name(&self) -> &'static str {
+        "inputbot"
+    }
+
+    fn register(&self, key: Key, callback: Callback) -> anyhow::Result<()> {
+        let trigger = trigger(key).ok_or_else(|| anyhow::anyhow!("inputbot 不支持按键 {}", key))?;
+        trigger.bind(move || callback());
+        Ok(())
+    }
+
+    fn unregister(&self, key: Key) {
+        if let Some(trigger) = trigger(key) {
+            trigger.unbind();
+        }
+    }
+
+    fn run(&self) {
+        ::inputbot::handle_input_events();
+    }
+}
+
+/// inputbot 中可以绑定回调的触发键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Trigger {
+    Keybd(KeybdKey),
+    Mouse(MouseButton),
+}
+
+impl Trigger {
+    fn bind<F: Fn() + Send + Sync + 'static>(self, callback: F) {
+        match self {
+            Trigger::Keybd(key) => key.bind(callback),
+            Trigger::Mouse(button) => button.bind(callback),
+        }
+    }
+
+    fn unbind(self) {
+        match self {
+            Trigger::Keybd(key) => key.unbind(),
+            Trigger::Mouse(button) => button.unbind(),
+        }
+    }
+}
+
+fn trigger(key: Key) -> Option<Trigger> {
+    let keybd_key = match key {
+        Key::Letter(c) => ::inputbot::get_keybd_key(c)?,
+        Key::Digit(n) => match n {
+            0 => KeybdKey::Numrow0Key,
+            1 => KeybdKey::Numrow1Key,
+            2 => KeybdKey::Numrow2Key,
+            3 => KeybdKey::Numrow3Key,
+            4 => KeybdKey::Numrow4Key,
+            5 => KeybdKey::Numrow5Key,
+            6 => KeybdKey::Numrow6Key,
+            7 => KeybdKey::Numrow7Key,
+            8 => KeybdKey::Numrow8Key,
+            9 => KeybdKey::Numrow9Key,
+            _ => return None,
+        },
+        Key::Numpad(n) => match n {
+            0 => KeybdKey::Numpad0Key,
+            1 => KeybdKey::Numpad1Key,
+            2 => KeybdKey::Numpad2Key,
+            3 => KeybdKey::Numpad3Key,
+            4 => KeybdKey::Numpad4Key,
+            5 => KeybdKey::Numpad5Key,
+            6 => KeybdKey::Numpad6Key,
+            7 => KeybdKey::Numpad7Key,
+            8 => KeybdKey::Numpad8Key,
+            9 => KeybdKey::Numpad9Key,
+            _ => return None,
+        },
+        Key::F(n) => match n {
+            1 => KeybdKey::F1Key,
+            2 => KeybdKey::F2Key,
+            3 => KeybdKey::F3Key,
+            4 => KeybdKey::F4Key,
+            5 => KeybdKey::F5Key,
+            6 => KeybdKey::F6Key,
+            7 => KeybdKey::F7Key,
+            8 => KeybdKey::F8Key,
+            9 => KeybdKey::F9Key,
+            10 => KeybdKey::F10Key,
+            11 => KeybdKey::F11Key,
+            12 => KeybdKey::F12Key,
+            13 => KeybdKey::F13Key,
+            14 => KeybdKey::F14Key,
+            15 => KeybdKey::F15Key,
+            16 => KeybdKey::F16Key,
+            17 => KeybdKey::F17Key,
+            18 => KeybdKey::F18Key,
+            19 => KeybdKey::F19Key,
+            20 => KeybdKey::F20Key,
+            21 => KeybdKey::F21Key,
+            22 => KeybdKey::F22Key,
+            23 => KeybdKey::F23Key,
+            24 => KeybdKey::F24Key,
+            _ => return None,
+        },
+        Key::Space => KeybdKey::SpaceKey,
+        Key::Enter => KeybdKey::EnterKey,
+        Key::Tab => KeybdKey::TabKey,
+        Key::Escape => KeybdKey::EscapeKey,
+        Key::Backspace => KeybdKey::BackspaceKey,
+        Key::Insert => KeybdKey::InsertKey,
+        Key::Delete => KeybdKey::DeleteKey,
+        Key::Home => KeybdKey::HomeKey,
+        Key::End => KeybdKey::EndKey,
+        Key::PageUp => KeybdKey::PageUpKey,
+        Key::PageDown => KeybdKey::PageDownKey,
+        Key::Up => KeybdKey::UpKey,
+        Key::Down => KeybdKey::DownKey,
+        Key::Left => KeybdKey::LeftKey,
+        Key::Right => KeybdKey::RightKey,
+        Key::Backquote => KeybdKey::BackquoteKey,
+        Key::Minus => KeybdKey::MinusKey,
+        Key::Equal => KeybdKey::EqualKey,
+        Key::LBracket => KeybdKey::LBracketKey,
+        Key::RBracket => KeybdKey::RBracketKey,
+        Key::Backslash => KeybdKey::BackslashKey,
+        Key::Semicolon => KeybdKey::SemicolonKey,
+        Key::Quote => KeybdKey::QuoteKey,
+        Key::Comma => KeybdKey::CommaKey,
+        Key::Period => KeybdKey::PeriodKey,
+        Key::Slash => KeybdKey::SlashKey,
+        // inputbot 没有为以下按键命名, 直接使用虚拟键码
+        Key::NumpadMultiply => KeybdKey::OtherKey(0x6A),
+        Key::NumpadAdd => KeybdKey::OtherKey(0x6B),
+        Key::NumpadSubtract => KeybdKey::OtherKey(0x6D),
+        Key::NumpadDecimal => KeybdKey::OtherKey(0x6E),
+        Key::NumpadDivide => KeybdKey::OtherKey(0x6F),
+        Key::IntlBackslash => KeybdKey::OtherKey(0xE2),
+        // 小键盘回车与主键盘回车的虚拟键码相同, Windows 键盘上也没有小键盘等号
+        Key::NumpadEnter | Key::NumpadEqual => return None,
+        Key::MouseMiddle => return Some(Trigger::Mouse(MouseButton::MiddleButton)),
+        Key::MouseX1 => return Some(Trigger::Mouse(MouseButton::X1Button)),
+        Key::MouseX2 => return Some(Trigger::Mouse(MouseButton::X2Button)),
+    };
+    Some(Trigger::Keybd(keybd_key))
+}
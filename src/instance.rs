@@ -0,0 +1,257 @@
+use anyhow::Result;
+use flume::Sender;
+use log::{error, info, warn};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cli::Cli;
+use crate::paths;
+use crate::token;
+use crate::tray::TrayMessage;
+
+const LOCK_FILE_NAME: &str = "instance.lock";
+/// 运行中实例的监听端口和本次运行的认证令牌
+const ENDPOINT_FILE_NAME: &str = "instance.endpoint";
+/// 以管理员权限重新启动时等待旧实例退出的最长时间
+const LOCK_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 转发命令时等待运行中的实例开始监听的最长时间
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+/// 读取转发命令的超时, 避免一个不发送数据的连接阻塞后续转发
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 可以转发给运行中实例的命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// 没有指定命令时重复启动, 提示程序已在运行
+    Activate,
+    Disconnect,
+    Degrade,
+    ShowSettings,
+    SelectProfile(String),
+}
+
+impl Command {
+    fn to_line(&self) -> String {
+        match self {
+            Command::Activate => "activate".to_string(),
+            Command::Disconnect => "disconnect".to_string(),
+            Command::Degrade => "degrade".to_string(),
+            Command::ShowSettings => "show-settings".to_string(),
+            Command::SelectProfile(name) => format!("profile {}", name),
+        }
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        match line {
+            "activate" => Some(Command::Activate),
+            "disconnect" => Some(Command::Disconnect),
+            "degrade" => Some(Command::Degrade),
+            "show-settings" => Some(Command::ShowSettings),
+            _ => line
+                .strip_prefix("profile ")
+                .map(|name| Command::SelectProfile(name.to_string())),
+        }
+    }
+
+    /// 在运行中的实例里对应的托盘消息
+    fn message(self) -> TrayMessage {
+        match self {
+            Command::Activate => TrayMessage::ShowStatus,
+            Command::Disconnect => TrayMessage::Reconnect,
+            Command::Degrade => TrayMessage::Degrade,
+            Command::ShowSettings => TrayMessage::Setting,
+            Command::SelectProfile(name) => TrayMessage::SelectProfile(name),
+        }
+    }
+}
+
+/// 命令行中需要转发的命令
+pub fn commands(cli: &Cli) -> Vec<Command> {
+    let mut commands = Vec::new();
+    if let Some(name) = &cli.profile {
+        commands.push(Command::SelectProfile(name.clone()));
+    }
+    if cli.disconnect {
+        commands.push(Command::Disconnect);
+    }
+    if cli.degrade {
+        commands.push(Command::Degrade);
+    }
+    if cli.show_settings {
+        commands.push(Command::ShowSettings);
+    }
+    if commands.is_empty() {
+        commands.push(Command::Activate);
+    }
+    commands
+}
+
+/// 单实例锁, 进程退出时由系统释放
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// 锁文件放在运行时目录而不是配置目录, 使用不同配置的副本也不能同时运行
+fn file_path(name: &str) -> Result<PathBuf> {
+    Ok(paths::runtime_dir()?.join(name))
+}
+
+/// 写入只有当前用户可以读取的文件
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let _ = std::fs::remove_file(path);
+    let mut options = File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// 获取单实例锁, 已有实例在运行时返回 `None`
+///
+/// `wait` 为真时等待旧实例退出, 用于以管理员权限重新启动
+pub fn acquire(wait: bool) -> Result<Option<InstanceLock>> {
+    let path = file_path(LOCK_FILE_NAME)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    let deadline = Instant::now() + if wait { LOCK_WAIT_TIMEOUT } else { Duration::ZERO };
+    loop {
+        match file.try_lock() {
+            Ok(_) => return Ok(Some(InstanceLock { _file: file })),
+            Err(std::fs::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(200));
+            }
+            Err(std::fs::TryLockError::WouldBlock) => return Ok(None),
+            Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+}
+
+impl InstanceLock {
+    /// 在本机回环地址上接收其他实例转发的命令, 转换为托盘消息
+    ///
+    /// 端口和随机令牌写入运行时目录中只有当前用户可读的文件, 转发方需要先发送 `auth <令牌>`
+    pub fn listen(&self, tx: Sender<TrayMessage>) -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let token = token::generate(32)?;
+        write_private(
+            &file_path(ENDPOINT_FILE_NAME)?,
+            &format!("{} {}", listener.local_addr()?.port(), token),
+        )?;
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .map_err(anyhow::Error::from)
+                    .and_then(|stream| receive(stream, &token, &tx));
+                if let Err(e) = result {
+                    error!("接收转发的命令失败: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+fn receive(stream: TcpStream, expected_token: &str, tx: &Sender<TrayMessage>) -> Result<()> {
+    stream.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    let authenticated = lines.next().transpose()?.is_some_and(|line| {
+        line.strip_prefix("auth ")
+            .is_some_and(|actual| token::matches(expected_token, actual))
+    });
+    if !authenticated {
+        let _ = writeln!(writer, "err 认证失败");
+        anyhow::bail!("转发方认证失败");
+    }
+    for line in lines {
+        let line = line?;
+        match Command::from_line(&line) {
+            Some(command) => {
+                info!("收到其他实例转发的命令: {:?}", command);
+                tx.send(command.message())?;
+            }
+            None => warn!("警告: 无法识别转发的命令: {}", line),
+        }
+    }
+    writeln!(writer, "ok")?;
+    Ok(())
+}
+
+/// 把命令转发给运行中的实例
+pub fn forward(commands: &[Command]) -> Result<()> {
+    let deadline = Instant::now() + FORWARD_TIMEOUT;
+    let mut stream = loop {
+        // 运行中的实例可能刚获取锁, 还没有写入端口
+        let connected = file_path(ENDPOINT_FILE_NAME)
+            .and_then(|path| Ok(std::fs::read_to_string(path)?))
+            .and_then(|endpoint| {
+                let (port, token) = endpoint
+                    .trim()
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow::anyhow!("无效的实例地址文件: {}", endpoint))?;
+                let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port.parse::<u16>()?))?;
+                Ok((stream, token.to_string()))
+            });
+        match connected {
+            Ok((mut stream, token)) => {
+                writeln!(stream, "auth {}", token)?;
+                break stream;
+            }
+            Err(e) if Instant::now() >= deadline => {
+                return Err(anyhow::anyhow!("无法连接运行中的实例: {}", e));
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(200)),
+        }
+    };
+    for command in commands {
+        writeln!(stream, "{}", command.to_line())?;
+    }
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    anyhow::ensure!(reply.trim_end() == "ok", "运行中的实例没有确认收到命令");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 通过回环连接发送 `lines`, 返回 `receive` 的结果、收到的托盘消息和回复
+    fn send(lines: &str) -> (Result<()>, Vec<TrayMessage>, String) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(lines.as_bytes()).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let (tx, rx) = flume::unbounded();
+        let result = receive(listener.accept().unwrap().0, "secret", &tx);
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).unwrap();
+        (result, rx.drain().collect(), reply)
+    }
+
+    #[test]
+    fn receive_requires_token() {
+        let (result, messages, reply) = send("auth secret\ndisconnect\n");
+        assert!(result.is_ok());
+        assert!(matches!(messages[..], [TrayMessage::Reconnect]));
+        assert_eq!(reply, "ok\n");
+
+        for lines in ["disconnect\n", "auth wrong\ndisconnect\n", ""] {
+            let (result, messages, reply) = send(lines);
+            assert!(result.is_err());
+            assert!(messages.is_empty());
+            assert_eq!(reply, "err 认证失败\n");
+        }
+    }
+}
@@ -1,19 +1,23 @@
 use anyhow::Result;
 use log::info;
 use simplelog::*;
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
+
+use crate::config;
+use crate::paths;
 
 pub fn init_logger() -> Result<()> {
-    // 获取exe所在目录
-    let exe_path = std::env::current_exe()?;
-    let exe_dir = exe_path.parent().unwrap();
-    let log_file_path = exe_dir.join("hsarec.log");
+    let log_file_path =
+        &config::log_file_override().unwrap_or_else(|| paths::get().log_file.clone());
+    if let Some(log_dir) = log_file_path.parent() {
+        fs::create_dir_all(log_dir)?;
+    }
 
     // 以追加模式打开日志文件
     let log_file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(&log_file_path)?;
+        .open(log_file_path)?;
 
     let config = ConfigBuilder::new()
         .add_filter_allow_str("hsarec")
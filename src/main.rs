@@ -1,44 +1,116 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::{
-    net::Ipv4Addr,
+    cell::{Cell, RefCell},
+    net::{Ipv4Addr, SocketAddrV4},
     sync::{Arc, Mutex},
+    time::Instant,
 };
-use tray_item::IconSource;
 
-use flume::{Selector, unbounded};
-use is_elevated::is_elevated;
+use clap::Parser;
+use flume::{Selector, TrySendError, bounded, unbounded};
 use log::{error, info, warn};
 
+mod autostart;
+mod cli;
 mod config;
+mod degrade;
+mod focus;
 mod gui;
 mod hearthstone;
+mod helper;
 mod hotkey;
+mod instance;
 mod logger;
 mod network;
+mod notification;
+mod overrides;
+mod paths;
+mod policy;
 mod process;
+mod reconnect;
+mod status;
+mod token;
 mod tray;
 
 const PROCESS_NAME: &str = "Hearthstone.exe";
 const LOGFILE_NAME: &str = "Hearthstone.log";
+/// 状态提示窗口的显示时长
+const STATUS_TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(3);
+/// 降级模式(没有管理员权限)的提示
+const DEGRADED_WARNING: &str =
+    "未获得管理员权限, 拔线功能已禁用。可以在托盘菜单中选择\"以管理员身份重新启动\"。";
+/// 无法获取前台窗口时的提示
+const FOCUS_GUARD_WARNING: &str = "无法获取前台窗口, 焦点保护不可用, 热键在任何窗口中都会生效。";
+/// 托盘菜单中游戏状态的刷新间隔
+const TRAY_STATUS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// 状态窗口打开时的刷新间隔
+const STATUS_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 slint::include_modules!();
 
 fn main() -> anyhow::Result<()> {
+    let cli = cli::Cli::parse();
+    if let Some(cli::Command::Helper { pipe, token }) = &cli.command {
+        return helper::run(pipe, token);
+    }
+    // 必须在创建任何线程之前获取本地时区
+    status::init_local_offset();
+    let instance_commands = instance::commands(&cli);
+    let app_paths = paths::init(cli.config)?;
+    let overrides = overrides::init(&cli.set);
+    if let Some(cli::Command::Config {
+        command: cli::ConfigCommand::Show,
+    }) = cli.command
+    {
+        cli::attach_console();
+        for error in overrides.errors() {
+            eprintln!("警告: {}", error);
+        }
+        print!("{}", config::show_sources()?);
+        return Ok(());
+    }
+    // 同时只运行一个实例, 重复启动时把命令行交给运行中的实例;
+    // 在初始化日志和迁移配置之前获取, 第二个实例不会改动运行中实例的文件
+    let Some(instance_lock) = instance::acquire(cli.wait_for_lock)? else {
+        instance::forward(&instance_commands)?;
+        return Ok(());
+    };
     logger::init_logger()?;
+    info!(
+        "配置文件: {:?}{}",
+        app_paths.config_file,
+        if app_paths.portable { " (便携模式)" } else { "" }
+    );
+    for error in overrides.errors() {
+        warn!("警告: {}", error);
+    }
+    paths::migrate_legacy_config();
 
-    if !is_elevated() {
-        error!("应用未以管理员权限运行, 软件无法正常工作。");
-        return Err(anyhow::anyhow!(
-            "应用未以管理员权限运行, 软件无法正常工作。"
-        ));
+    // 未以管理员权限运行时由辅助进程代为关闭连接, 界面本身不需要管理员权限
+    let closer: Arc<dyn helper::ConnectionCloser> = Arc::from(helper::default_closer());
+    info!("关闭连接的方式: {}", closer.name());
+    // 无法关闭连接时仍然运行, 保留日志监控和状态功能
+    let degraded = !closer.is_available();
+    if degraded {
+        warn!("警告: {}", DEGRADED_WARNING);
     }
 
     let hs_ip: Arc<Mutex<Option<Ipv4Addr>>> = Arc::new(Mutex::new(None));
     let hs_port: Arc<Mutex<Option<u16>>> = Arc::new(Mutex::new(None));
-    let is_disconnecting = Arc::new(Mutex::new(false));
+    let reconnect_state = Arc::new(Mutex::new(reconnect::ReconnectState::new()));
 
     let app_config = config::get_config();
+    if let Some(name) = &cli.profile {
+        let mut config = app_config
+            .write()
+            .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+        config.select_profile(name)?;
+        if let Err(e) = config.save() {
+            warn!("警告: 无法保存配置档选择: {}", e);
+        }
+        info!("已切换到配置档: {}", name);
+    }
     info!(
         "加载的配置: {:?}",
         app_config
@@ -54,31 +126,56 @@ fn main() -> anyhow::Result<()> {
     let (gui_in_tx, gui_in_rx) = unbounded::<gui::GuiInMessage>();
     // 日志监控线程
     let (log_tx, log_rx) = unbounded::<hearthstone::LogMessage>();
+    // 配置文件监控线程
+    let (config_tx, config_rx) = unbounded::<Result<config::Config, config::ConfigErrors>>();
 
-    let reconnect_hotkey = app_config
+    let initial_config = app_config
         .read()
         .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
-        .reconnect_hotkey
         .clone();
-    let tray_item = Arc::new(Mutex::new(tray::setup_tray(
+    instance_lock.listen(tray_tx.clone())?;
+    // 本实例启动时也执行命令行中的操作, --profile 已在上面处理
+    if cli.disconnect {
+        tray_tx.send(tray::TrayMessage::Reconnect)?;
+    }
+    if cli.degrade {
+        tray_tx.send(tray::TrayMessage::Degrade)?;
+    }
+    if cli.show_settings {
+        tray_tx.send(tray::TrayMessage::Setting)?;
+    }
+    if let Err(e) = autostart::sync(initial_config.auto_start) {
+        warn!("警告: 无法修改开机自动启动: {}", e);
+    }
+    let tray_backend = tray::default_backend().unwrap_or_else(|e| {
+        warn!("警告: 无法创建托盘图标, 将在没有托盘的情况下运行: {}", e);
+        Box::new(tray::NoopBackend::new())
+    });
+    info!("使用托盘实现: {}", tray_backend.name());
+    let tray_item = Arc::new(Mutex::new(tray::Tray::new(
+        tray_backend,
         tray_tx.clone(),
-        &reconnect_hotkey,
+        &initial_config,
+        &tray::TrayStatus::default(),
+        degraded,
     )?));
 
-    let reconnect_hotkey_clone = reconnect_hotkey.clone();
-    let (main_key_opt, modifier_keys_vec) = hotkey::parse_hotkey_config(&reconnect_hotkey_clone);
-    let mut current_registered_key: Option<inputbot::KeybdKey> = None;
-    if let Some(main_key_to_bind) = main_key_opt {
-        hotkey::register_hotkey(tray_tx.clone(), main_key_to_bind, modifier_keys_vec);
-        current_registered_key = Some(main_key_to_bind);
-    } else {
-        warn!(
-            "警告: 无法从配置文件解析或注册主热键: {}。将不会注册热键。",
-            reconnect_hotkey_clone
-        );
-    }
+    let hotkey_backend = hotkey::default_backend();
+    info!("使用热键实现: {}", hotkey_backend.name());
+    let focus_guard = Arc::new(focus::FocusGuard::new(focus::default_provider()));
+    let current_registered_keys =
+        RefCell::new(hotkey::register_hotkeys(
+            &hotkey_backend,
+            tray_tx.clone(),
+            &initial_config.hotkeys,
+            initial_config.strict_hotkeys,
+            initial_config.chord_timeout(),
+            initial_config.focus_guard.then(|| Arc::clone(&focus_guard)),
+        ));
+    let degrader = degrade::Degrader::new(degrade::default_backend(&initial_config.degrade));
+    let backend = Arc::clone(&hotkey_backend);
     std::thread::spawn(move || {
-        inputbot::handle_input_events();
+        backend.run();
     });
     let log_tx_clone = log_tx.clone();
     std::thread::spawn(move || {
@@ -95,13 +192,167 @@ fn main() -> anyhow::Result<()> {
             }
         }
     });
+    // 在单独的线程中检测炉石进程, 避免在消息循环中枚举进程
+    let (game_tx, game_rx) = unbounded::<Option<u32>>();
+    std::thread::spawn(move || hearthstone::watch_process(game_tx));
+    // 状态窗口中的网络连接在单独的线程中查询, 查询期间最多再排队一次刷新
+    let (status_tx, status_rx) = bounded::<status::StatusSnapshot>(1);
+    let gui_in_tx_status = gui_in_tx.clone();
+    std::thread::spawn(move || {
+        for mut snapshot in status_rx {
+            if let Ok(pid) = snapshot.pid {
+                snapshot.connections = list_connections(pid, snapshot.server);
+            }
+            if gui_in_tx_status
+                .send(gui::GuiInMessage::StatusUpdated(snapshot))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    // 定时刷新托盘菜单中的游戏状态
+    let (tray_tick_tx, tray_tick_rx) = unbounded::<()>();
+    std::thread::spawn(move || {
+        while tray_tick_tx.send(()).is_ok() {
+            std::thread::sleep(TRAY_STATUS_INTERVAL);
+        }
+    });
+    std::thread::spawn(move || {
+        if let Err(e) = config::watch_config(config_tx) {
+            error!("配置文件监控线程意外退出, 修改配置后需要重启程序才能生效。错误: {}", e);
+        }
+    });
 
     let hs_ip_clone = Arc::clone(&hs_ip);
     let hs_port_clone = Arc::clone(&hs_port);
     let gui_in_tx_clone = gui_in_tx.clone();
-    let is_disconnecting_clone = Arc::clone(&is_disconnecting);
+    let reconnect_state_clone = Arc::clone(&reconnect_state);
     let tray_item_clone = Arc::clone(&tray_item);
+    let degrader_exit = Arc::clone(&degrader);
+    let focus_guard_settings = Arc::clone(&focus_guard);
     std::thread::spawn(move || -> anyhow::Result<()> {
+        let disconnect_policy = RefCell::new(policy::DisconnectPolicy::new());
+        let disconnect_history = RefCell::new(status::History::new());
+        // 进程检测线程最近一次报告的炉石 PID
+        let game_pid = Cell::new(None);
+        // 托盘菜单等界面当前是否按降级模式显示
+        let degraded_shown = Cell::new(degraded);
+        // 刷新托盘菜单中的游戏状态和图标
+        let refresh_tray_status = || -> anyhow::Result<()> {
+            let server = match (
+                *hs_ip_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?,
+                *hs_port_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?,
+            ) {
+                (Some(ip), Some(port)) => Some(SocketAddrV4::new(ip, port)),
+                _ => None,
+            };
+            let status = tray::TrayStatus {
+                pid: game_pid.get(),
+                server,
+                disconnects: disconnect_policy.borrow().count(),
+            };
+            let icon = {
+                let mut state = reconnect_state_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?;
+                state.tick(status.pid.is_some(), Instant::now());
+                state.icon()
+            };
+            let mut tray = tray_item_clone
+                .lock()
+                .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?;
+            tray.update_status(&status)?;
+            tray.set_icon(icon)?;
+            Ok(())
+        };
+        // 按当前配置发送桌面通知
+        let send_notification = |event: notification::Event| match app_config.read() {
+            Ok(config) => notification::notify(&config.notifications, event),
+            Err(e) => error!("无法获取配置读取锁: {}", e),
+        };
+        // 辅助进程启动失败(如用户拒绝 UAC 授权)后转为降级模式, 更新托盘菜单并提示, 返回是否处于降级模式
+        let refresh_degraded = || -> anyhow::Result<bool> {
+            let degraded = !closer.is_available();
+            if degraded != degraded_shown.replace(degraded) {
+                if degraded {
+                    warn!("警告: {}", DEGRADED_WARNING);
+                    gui_in_tx_clone
+                        .send(gui::GuiInMessage::ShowStatus(DEGRADED_WARNING.to_string()))
+                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                }
+                let config = app_config
+                    .read()
+                    .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?;
+                tray_item_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?
+                    .set_degraded(&config, degraded)?;
+            }
+            Ok(degraded)
+        };
+        // 收集状态窗口展示的内容, 网络连接由状态线程补充后发给界面
+        let request_status = || -> anyhow::Result<()> {
+            let server = match (
+                *hs_ip_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?,
+                *hs_port_clone
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?,
+            ) {
+                (Some(ip), Some(port)) => Some(SocketAddrV4::new(ip, port)),
+                _ => None,
+            };
+            let state = reconnect_state_clone
+                .lock()
+                .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                .to_string();
+            let focus_guard_enabled = app_config
+                .read()
+                .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
+                .focus_guard;
+            let warnings: Vec<&str> = [
+                degraded_shown.get().then_some(DEGRADED_WARNING),
+                (focus_guard_enabled && !focus_guard.is_available()).then_some(FOCUS_GUARD_WARNING),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            let snapshot = status::StatusSnapshot {
+                pid: game_pid
+                    .get()
+                    .ok_or_else(|| format!("没有找到名为 {} 的进程。", PROCESS_NAME)),
+                server,
+                connections: Vec::new(),
+                state,
+                warning: (!warnings.is_empty()).then(|| warnings.join("\n")),
+                history: disconnect_history.borrow().records(),
+            };
+            // 上一次刷新还在排队时忽略本次请求
+            match status_tx.try_send(snapshot) {
+                Err(TrySendError::Disconnected(_)) => Err(anyhow::anyhow!("状态线程已退出")),
+                _ => Ok(()),
+            }
+        };
+        // 按新配置重新注册全部热键
+        let reload_hotkeys = |config: &config::Config| {
+            let mut keys = current_registered_keys.borrow_mut();
+            info!("正在注销当前热键: {:?}", *keys);
+            hotkey::unregister_hotkeys(&hotkey_backend, &keys);
+            *keys = hotkey::register_hotkeys(
+                &hotkey_backend,
+                tray_tx.clone(),
+                &config.hotkeys,
+                config.strict_hotkeys,
+                config.chord_timeout(),
+                config.focus_guard.then(|| Arc::clone(&focus_guard)),
+            );
+        };
         loop {
             let selector_res = Selector::new()
                 .recv(&tray_rx, |msg| -> anyhow::Result<()> {
@@ -115,30 +366,237 @@ fn main() -> anyhow::Result<()> {
                                         .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
                                     return Err(anyhow::anyhow!("Exit signal received"));
                                 }
-                                tray::TrayMessage::Reconnect => {
-                                    let hs_ip = hs_ip_clone
+                                tray::TrayMessage::Reconnect | tray::TrayMessage::ReconnectAll => {
+                                    let profile = app_config
+                                        .read()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
+                                        .active_profile();
+                                    let now = Instant::now();
+                                    let all = matches!(tray_msg, tray::TrayMessage::ReconnectAll)
+                                        || profile.strategy == config::DisconnectStrategy::All;
+                                    let action = if all { "断开全部连接" } else { "断开游戏服务器" };
+                                    if refresh_degraded()? {
+                                        warn!("没有管理员权限, 已忽略拔线请求。");
+                                        disconnect_history
+                                            .borrow_mut()
+                                            .push(action, status::Outcome::Rejected("没有管理员权限".to_string()), now);
+                                        gui_in_tx_clone
+                                            .send(gui::GuiInMessage::ShowStatus(DEGRADED_WARNING.to_string()))
+                                            .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                        return Ok(());
+                                    }
+                                    if let Err(e) = disconnect_policy.borrow().check(&profile, now) {
+                                        warn!("已忽略拔线请求: {}", e);
+                                        disconnect_history
+                                            .borrow_mut()
+                                            .push(action, status::Outcome::Rejected(e.to_string()), now);
+                                        if let policy::Rejection::CapReached { .. } = e {
+                                            send_notification(notification::Event::CapReached(e.to_string()));
+                                        }
+                                        return Ok(());
+                                    }
+                                    reconnect_state_clone
                                         .lock()
-                                        .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?;
-                                    let hs_port = hs_port_clone
+                                        .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                                        .start();
+                                    // 只切换图标, 其余状态等连接关闭后再刷新, 不拖慢拔线
+                                    tray_item_clone
                                         .lock()
-                                        .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?;
-                                    match hearthstone::reconnect(*hs_ip, *hs_port) {
+                                        .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?
+                                        .set_icon(tray::TrayIcon::Disconnecting)?;
+                                    let result = if all {
+                                        hearthstone::reconnect_all(closer.as_ref())
+                                    } else {
+                                        let hs_ip = *hs_ip_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?;
+                                        let hs_port = *hs_port_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?;
+                                        hearthstone::reconnect(hs_ip, hs_port, closer.as_ref())
+                                    };
+                                    match result {
                                         Ok(_) => {
-                                            info!("重连操作成功。");
-                                            let mut is_disconnecting = is_disconnecting_clone
-                                                .lock()
-                                                .map_err(|e| anyhow::anyhow!("无法获取断开连接状态锁: {}", e))?;
-                                            *is_disconnecting = true;
-                                            let mut tray = tray_item_clone
+                                            if all {
+                                                info!("已断开炉石的全部连接。");
+                                            } else {
+                                                info!("重连操作成功。");
+                                            }
+                                            disconnect_policy.borrow_mut().record(now);
+                                            disconnect_history
+                                                .borrow_mut()
+                                                .push(action, status::Outcome::Pending, now);
+                                            send_notification(notification::Event::DisconnectStarted(action.to_string()));
+                                            // 用完本局的次数时立即提醒, 而不是等到下一次拔线被拒绝
+                                            if let Err(e @ policy::Rejection::CapReached { .. }) =
+                                                disconnect_policy.borrow().check(&profile, now)
+                                            {
+                                                send_notification(notification::Event::CapReached(e.to_string()));
+                                            }
+                                            reconnect_state_clone
                                                 .lock()
-                                                .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?;
-                                            tray.set_icon(IconSource::Resource("#3"))?;
+                                                .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                                                .disconnected(Instant::now());
                                         }
                                         Err(e) => {
                                             error!("重连操作失败: {}", e);
+                                            disconnect_history
+                                                .borrow_mut()
+                                                .push(action, status::Outcome::Failed(e.to_string()), now);
+                                            send_notification(notification::Event::Failed(e.to_string()));
+                                            reconnect_state_clone
+                                                .lock()
+                                                .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                                                .failed(e.to_string(), Instant::now());
+                                        }
+                                    }
+                                    refresh_degraded()?;
+                                    refresh_tray_status()?;
+                                }
+                                tray::TrayMessage::SelectProfile(name) => {
+                                    let mut config = app_config
+                                        .write()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                    // 其他实例转发的配置档名称可能不存在
+                                    if let Err(e) = config.select_profile(&name) {
+                                        warn!("警告: {}", e);
+                                        return Ok(());
+                                    }
+                                    info!("已切换到配置档: {}", name);
+                                    config.save()?;
+                                    tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
+                                tray::TrayMessage::SetStrategy(strategy) => {
+                                    let mut config = app_config
+                                        .write()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                    let name = config.profile.clone();
+                                    let mut profile = config.active_profile();
+                                    profile.strategy = strategy;
+                                    config.profiles.insert(name.clone(), profile);
+                                    info!("配置档 {} 的拔线方式已改为 {:?}", name, strategy);
+                                    config.save()?;
+                                    tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
+                                tray::TrayMessage::RestartAsAdmin => match helper::restart_elevated() {
+                                    Ok(_) => {
+                                        info!("已以管理员权限启动新的实例, 正在退出...");
+                                        tray_tx.send(tray::TrayMessage::Exit)?;
+                                    }
+                                    Err(e) => {
+                                        error!("无法以管理员权限重新启动: {}", e);
+                                        gui_in_tx_clone
+                                            .send(gui::GuiInMessage::ShowStatus(format!("无法以管理员权限重新启动: {}", e)))
+                                            .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                    }
+                                },
+                                tray::TrayMessage::ToggleAutoStart => {
+                                    let mut config = app_config
+                                        .write()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                    let enabled = !config.auto_start;
+                                    if let Err(e) = autostart::sync(enabled) {
+                                        error!("无法修改开机自动启动: {}", e);
+                                        return Ok(());
+                                    }
+                                    config.auto_start = enabled;
+                                    config.save()?;
+                                    tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
+                                tray::TrayMessage::ToggleExitWithGame => {
+                                    let mut config = app_config
+                                        .write()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                    config.exit_with_game = !config.exit_with_game;
+                                    info!(
+                                        "炉石退出时自动退出已{}",
+                                        if config.exit_with_game { "开启" } else { "关闭" }
+                                    );
+                                    config.save()?;
+                                    tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
+                                tray::TrayMessage::ShowStatus => {
+                                    let server = match (
+                                        *hs_ip_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?,
+                                        *hs_port_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?,
+                                    ) {
+                                        (Some(ip), Some(port)) => format!("{}:{}", ip, port),
+                                        _ => "未检测到".to_string(),
+                                    };
+                                    let state = reconnect_state_clone
+                                        .lock()
+                                        .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                                        .to_string();
+                                    let profile = app_config
+                                        .read()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
+                                        .profile
+                                        .clone();
+                                    let status = format!(
+                                        "游戏服务器: {}\n拔线状态: {}\n本局拔线: {} 次\n配置档: {}",
+                                        server,
+                                        state,
+                                        disconnect_policy.borrow().count(),
+                                        profile
+                                    );
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ShowStatus(status))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
+                                tray::TrayMessage::Degrade => {
+                                    if refresh_degraded()? {
+                                        warn!("没有管理员权限, 已忽略弱网模拟请求。");
+                                        gui_in_tx_clone
+                                            .send(gui::GuiInMessage::ShowStatus(DEGRADED_WARNING.to_string()))
+                                            .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                        return Ok(());
+                                    }
+                                    let endpoint = match (
+                                        *hs_ip_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石IP锁: {}", e))?,
+                                        *hs_port_clone
+                                            .lock()
+                                            .map_err(|e| anyhow::anyhow!("无法获取炉石端口锁: {}", e))?,
+                                    ) {
+                                        (Some(ip), Some(port)) => SocketAddrV4::new(ip, port),
+                                        _ => {
+                                            error!("尚未从日志中获取到炉石服务器地址, 无法模拟弱网。");
+                                            return Ok(());
                                         }
+                                    };
+                                    let params = degrade::DegradeParams::from(
+                                        &app_config
+                                            .read()
+                                            .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
+                                            .degrade,
+                                    );
+                                    if let Err(e) = degrader.start(endpoint, params) {
+                                        error!("弱网模拟失败: {}", e);
                                     }
                                 }
+                                tray::TrayMessage::StatusWindow => {
+                                    request_status()?;
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ShowStatusWindow)
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                }
                                 tray::TrayMessage::Setting => {
                                     gui_in_tx_clone
                                         .send(gui::GuiInMessage::Show)
@@ -148,7 +606,7 @@ fn main() -> anyhow::Result<()> {
                                     let mut tray = tray_item_clone
                                         .lock()
                                         .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?;
-                                    *tray = tray::setup_tray(tray_tx.clone(), &config)?;
+                                    tray.update_config(&config)?;
                                 }
                             }
                         }
@@ -159,35 +617,58 @@ fn main() -> anyhow::Result<()> {
                 .recv(&gui_out_rx, |msg| -> anyhow::Result<()> {
                     match msg {
                         Ok(gui_msg) => match gui_msg {
-                            gui::GuiOutMessage::SaveHotKeys(reconnect_hotkey) => {
+                            gui::GuiOutMessage::SaveHotKeys(action, combo) => {
                                 let mut config = app_config
                                     .write()
                                     .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
-                                config.reconnect_hotkey = reconnect_hotkey.clone();
+                                info!("已将「{}」的热键设置为 {}", action.label(), combo);
+                                config.set_hotkey(action, combo);
                                 config.save()?;
-                                tray_tx.send(tray::TrayMessage::UpdateMenu(
-                                    reconnect_hotkey.clone(),
-                                ))?;
-                                let (main_key_opt, modifier_keys_vec) =
-                                    hotkey::parse_hotkey_config(&reconnect_hotkey);
-                                if let Some(main_key_to_bind) = main_key_opt {
-                                    if let Some(current_key) = current_registered_key {
-                                        info!("正在注销当前热键: {:?}", current_key);
-                                        hotkey::unregister_hotkey(current_key);
-                                    }
-                                    hotkey::register_hotkey(
-                                        tray_tx.clone(),
-                                        main_key_to_bind,
-                                        modifier_keys_vec,
-                                    );
-                                    current_registered_key = Some(main_key_to_bind);
-                                } else {
-                                    warn!(
-                                        "警告: 无法从配置文件解析或注册主热键: {}。将不会注册热键。",
-                                        reconnect_hotkey_clone
-                                    );
+                                tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                reload_hotkeys(&config);
+                                gui_in_tx_clone
+                                    .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                    .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                            }
+                            gui::GuiOutMessage::SaveProfile(profile) => {
+                                let mut config = app_config
+                                    .write()
+                                    .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                let name = config.profile.clone();
+                                info!("已更新配置档 {}: {:?}", name, profile);
+                                config.profiles.insert(name, profile);
+                                config.save()?;
+                            }
+                            gui::GuiOutMessage::SaveGeneral(mut settings) => {
+                                let mut config = app_config
+                                    .write()
+                                    .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                                // 修改系统启动项失败时保留原来的选项, 并把设置窗口中的复选框改回去
+                                let mut auto_start_failed = false;
+                                if settings.auto_start != config.auto_start
+                                    && let Err(e) = autostart::sync(settings.auto_start)
+                                {
+                                    error!("无法修改开机自动启动: {}", e);
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ShowStatus(format!("无法修改开机自动启动: {}", e)))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                                    settings.auto_start = config.auto_start;
+                                    auto_start_failed = true;
+                                }
+                                let log_file_changed = config.log_file != settings.log_file;
+                                let language_changed = settings.apply(&mut config);
+                                config.save()?;
+                                tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                                if log_file_changed {
+                                    info!("日志文件路径已修改, 重启后生效。");
+                                }
+                                if language_changed || auto_start_failed {
+                                    gui_in_tx_clone
+                                        .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                        .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
                                 }
                             }
+                            gui::GuiOutMessage::RefreshStatus => request_status()?,
                         },
                         Err(e) => error!("接收GUI消息失败: {}", e),
                     }
@@ -199,22 +680,94 @@ fn main() -> anyhow::Result<()> {
                             info!("检测到炉石IP变化: {:?}", log_msg);
                             *hs_ip_clone.lock().unwrap() = log_msg.ip;
                             *hs_port_clone.lock().unwrap() = Some(log_msg.port);
-                            let mut is_disconnecting = is_disconnecting_clone
+                            let was_waiting = reconnect_state_clone
                                 .lock()
-                                .map_err(|e| anyhow::anyhow!("无法获取断开连接状态锁: {}", e))?;
-                            if *is_disconnecting {
-                                *is_disconnecting = false;
-                                let mut tray = tray_item_clone
-                                    .lock()
-                                    .map_err(|e| anyhow::anyhow!("无法获取托盘项目锁: {}", e))?;
-                                tray.set_icon(IconSource::Resource("#1"))?;
-                                info!("已恢复默认托盘图标。");
+                                .map_err(|e| anyhow::anyhow!("无法获取拔线状态锁: {}", e))?
+                                .reconnected();
+                            if was_waiting {
+                                if let Some(duration) = disconnect_history.borrow_mut().reconnected(Instant::now()) {
+                                    info!("游戏已在 {:.1} 秒后重新连接。", duration.as_secs_f32());
+                                    send_notification(notification::Event::Reconnected(duration));
+                                }
+                            } else {
+                                // 不是拔线后的重连, 说明进入了新的一局
+                                disconnect_policy.borrow_mut().new_game();
                             }
+                            refresh_tray_status()?;
                         }
                         Err(e) => error!("接收日志消息失败: {}", e),
                     }
                     Ok(())
                 })
+                .recv(&game_rx, |msg| -> anyhow::Result<()> {
+                    match msg {
+                        Ok(pid) => {
+                            match pid {
+                                Some(pid) => info!("检测到炉石进程, PID: {}", pid),
+                                None if game_pid.get().is_some() => {
+                                    info!("炉石进程已退出");
+                                    disconnect_history.borrow_mut().game_exited();
+                                    // 只有本次运行中见过炉石进程才随之退出, 以免先于游戏启动时直接退出
+                                    let exit_with_game = app_config
+                                        .read()
+                                        .map_err(|e| anyhow::anyhow!("无法获取配置读取锁: {}", e))?
+                                        .exit_with_game;
+                                    if exit_with_game {
+                                        info!("炉石已退出, 程序随之退出。");
+                                        tray_tx.send(tray::TrayMessage::Exit)?;
+                                    }
+                                }
+                                None => {}
+                            }
+                            game_pid.set(pid);
+                            focus_guard.set_game_pid(pid);
+                            refresh_tray_status()?;
+                        }
+                        Err(e) => error!("接收进程消息失败: {}", e),
+                    }
+                    Ok(())
+                })
+                .recv(&tray_tick_rx, |_| refresh_tray_status())
+                .recv(&config_rx, |msg| -> anyhow::Result<()> {
+                    match msg {
+                        Ok(Ok(new_config)) => {
+                            let mut config = app_config
+                                .write()
+                                .map_err(|e| anyhow::anyhow!("无法获取配置写入锁: {}", e))?;
+                            let changes = config.diff(&new_config);
+                            if changes.is_empty() {
+                                return Ok(());
+                            }
+                            for change in &changes {
+                                info!("配置已修改: {}", change);
+                            }
+                            if new_config.auto_start != config.auto_start
+                                && let Err(e) = autostart::sync(new_config.auto_start)
+                            {
+                                warn!("警告: 无法修改开机自动启动: {}", e);
+                            }
+                            if new_config.degrade != config.degrade
+                                && let Err(e) =
+                                    degrader.set_backend(degrade::default_backend(&new_config.degrade))
+                            {
+                                warn!("警告: 切换弱网模拟设置时无法撤销进行中的模拟: {}", e);
+                            }
+                            *config = new_config;
+                            tray_tx.send(tray::TrayMessage::UpdateMenu(config.clone()))?;
+                            gui_in_tx_clone
+                                .send(gui::GuiInMessage::ConfigChanged(config.clone()))
+                                .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                            reload_hotkeys(&config);
+                        }
+                        Ok(Err(errors)) => {
+                            gui_in_tx_clone
+                                .send(gui::GuiInMessage::ConfigErrors(errors.0))
+                                .map_err(|e| anyhow::anyhow!("无法发送GUI消息: {}", e))?;
+                        }
+                        Err(e) => error!("接收配置消息失败: {}", e),
+                    }
+                    Ok(())
+                })
                 .wait();
 
             if let Err(e) = selector_res {
@@ -228,27 +781,91 @@ fn main() -> anyhow::Result<()> {
         Ok(())
     });
 
-    let reconnect_hotkey_clone = reconnect_hotkey.clone();
-
-    let main_window = MainWindow::new()?;
-    main_window.hide()?;
-    let main_window_weak = main_window.as_weak();
-    main_window.set_hotkeys(reconnect_hotkey_clone.into());
-    main_window.on_save_hotkeys(move |value| {
-        let value_clone = value.clone();
-        if let Ok(_) = gui_out_tx.send(gui::GuiOutMessage::SaveHotKeys(value.into())) {
-            if let Some(main_window) = main_window_weak.upgrade() {
-                main_window.set_hotkeys(value_clone.into());
+    let settings_window = SettingsWindow::new()?;
+    settings_window.hide()?;
+    gui::load_settings(&settings_window, &initial_config);
+    gui::select_language(initial_config.language);
+    settings_window.on_key_name(|text| gui::key_name(&text).into());
+    settings_window.on_validate_hotkey(|text| gui::validate_hotkey(&text).into());
+    settings_window.on_hotkey_conflicts(|text, action| {
+        match config::get_config().read() {
+            Ok(config) => {
+                gui::hotkey_conflicts(&text, gui::hotkey_action(action), &config.hotkeys).into()
+            }
+            Err(_) => Default::default(),
+        }
+    });
+    let gui_out_tx_clone = gui_out_tx.clone();
+    settings_window.on_save_hotkeys(move |value, action| {
+        let hotkey = match value.parse::<hotkey::HotkeySequence>() {
+            Ok(hotkey) => hotkey.to_string(),
+            Err(e) => {
+                error!("热键 '{}' 无效: {}", value, e);
+                return;
+            }
+        };
+        let action = gui::hotkey_action(action);
+        if let Err(e) = gui_out_tx_clone.send(gui::GuiOutMessage::SaveHotKeys(action, hotkey)) {
+            error!("无法发送GUI消息: {}", e);
+        }
+    });
+    let settings_window_weak = settings_window.as_weak();
+    let gui_out_tx_clone = gui_out_tx.clone();
+    settings_window.on_save_disconnect_settings(move || {
+        if let Some(settings_window) = settings_window_weak.upgrade() {
+            let profile = gui::profile_settings(&settings_window);
+            if let Err(e) = gui_out_tx_clone.send(gui::GuiOutMessage::SaveProfile(profile)) {
+                error!("无法发送GUI消息: {}", e);
+            }
+        }
+    });
+    let settings_window_weak = settings_window.as_weak();
+    let gui_out_tx_clone = gui_out_tx.clone();
+    settings_window.on_save_general_settings(move || {
+        if let Some(settings_window) = settings_window_weak.upgrade() {
+            let settings = gui::general_settings(&settings_window);
+            if let Err(e) = gui_out_tx_clone.send(gui::GuiOutMessage::SaveGeneral(settings)) {
+                error!("无法发送GUI消息: {}", e);
             }
         }
     });
 
-    main_window.window().on_close_requested(move || {
-        info!("主窗口请求关闭，正在处理...");
+    let status_window = StatusWindow::new()?;
+    let status_window_weak = status_window.as_weak();
+    // 状态窗口打开时定时刷新
+    let status_refresh_timer = slint::Timer::default();
+    status_refresh_timer.start(slint::TimerMode::Repeated, STATUS_REFRESH_INTERVAL, move || {
+        if let Some(status_window) = status_window_weak.upgrade()
+            && status_window.window().is_visible()
+        {
+            let _ = gui_out_tx.send(gui::GuiOutMessage::RefreshStatus);
+        }
+    });
+
+    settings_window.window().on_close_requested(move || {
+        info!("设置窗口请求关闭，正在处理...");
         slint::CloseRequestResponse::HideWindow
     });
 
-    let main_window_weak = main_window.as_weak();
+    let status_toast = StatusToast::new()?;
+    let status_toast_weak = status_toast.as_weak();
+    let config_error_dialog = ConfigErrorDialog::new()?;
+    config_error_dialog.set_path(app_paths.config_file.display().to_string().into());
+    let config_error_dialog_weak = config_error_dialog.as_weak();
+    config_error_dialog.on_close(move || {
+        if let Some(dialog) = config_error_dialog_weak.upgrade() {
+            let _ = dialog.hide();
+        }
+    });
+    let config_error_dialog_weak = config_error_dialog.as_weak();
+    if let Some(errors) = config::take_load_errors() {
+        gui_in_tx.send(gui::GuiInMessage::ConfigErrors(errors.0))?;
+    }
+    if degraded {
+        gui_in_tx.send(gui::GuiInMessage::ShowStatus(DEGRADED_WARNING.to_string()))?;
+    }
+    let settings_window_weak = settings_window.as_weak();
+    let status_window_weak = status_window.as_weak();
     std::thread::spawn(move || {
         for message in &gui_in_rx {
             match message {
@@ -257,22 +874,115 @@ fn main() -> anyhow::Result<()> {
                     break;
                 }
                 gui::GuiInMessage::Show => {
-                    let window_weak_clone = main_window_weak.clone();
+                    // 打开设置窗口时检查焦点保护能否生效
+                    let focus_guard_warning = config::get_config()
+                        .read()
+                        .is_ok_and(|config| config.focus_guard)
+                        && !focus_guard_settings.is_available();
+                    let window_weak_clone = settings_window_weak.clone();
                     slint::invoke_from_event_loop(move || {
-                        if let Some(main_window) = window_weak_clone.upgrade() {
-                            let _ = main_window.show();
+                        if let Some(settings_window) = window_weak_clone.upgrade() {
+                            settings_window.set_focus_guard_warning(if focus_guard_warning {
+                                FOCUS_GUARD_WARNING.into()
+                            } else {
+                                Default::default()
+                            });
+                            let _ = settings_window.show();
                         } else {
                             error!("无法显示窗口, 似乎窗口已被销毁");
                         }
                     })
                     .unwrap();
                 }
+                gui::GuiInMessage::ConfigChanged(config) => {
+                    let window_weak_clone = settings_window_weak.clone();
+                    slint::invoke_from_event_loop(move || {
+                        gui::select_language(config.language);
+                        if let Some(settings_window) = window_weak_clone.upgrade() {
+                            gui::load_settings(&settings_window, &config);
+                        }
+                    })
+                    .unwrap();
+                }
+                gui::GuiInMessage::ConfigErrors(errors) => {
+                    let dialog_weak_clone = config_error_dialog_weak.clone();
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(dialog) = dialog_weak_clone.upgrade() {
+                            let rows: Vec<ConfigErrorRow> = errors
+                                .into_iter()
+                                .map(|e| ConfigErrorRow {
+                                    field: e.field.into(),
+                                    value: e.value.into(),
+                                    reason: e.reason.into(),
+                                })
+                                .collect();
+                            dialog.set_errors(slint::ModelRc::new(slint::VecModel::from(rows)));
+                            let _ = dialog.show();
+                        }
+                    })
+                    .unwrap();
+                }
+                gui::GuiInMessage::ShowStatusWindow => {
+                    let window_weak_clone = status_window_weak.clone();
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(status_window) = window_weak_clone.upgrade() {
+                            let _ = status_window.show();
+                        }
+                    })
+                    .unwrap();
+                }
+                gui::GuiInMessage::StatusUpdated(snapshot) => {
+                    let window_weak_clone = status_window_weak.clone();
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(status_window) = window_weak_clone.upgrade() {
+                            gui::show_status(&status_window, &snapshot);
+                        }
+                    })
+                    .unwrap();
+                }
+                gui::GuiInMessage::ShowStatus(status) => {
+                    let toast_weak_clone = status_toast_weak.clone();
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(toast) = toast_weak_clone.upgrade() {
+                            toast.set_message(status.into());
+                            let _ = toast.show();
+                            let toast_weak = toast.as_weak();
+                            slint::Timer::single_shot(STATUS_TOAST_DURATION, move || {
+                                if let Some(toast) = toast_weak.upgrade() {
+                                    let _ = toast.hide();
+                                }
+                            });
+                        }
+                    })
+                    .unwrap();
+                }
             }
         }
     });
 
-    // main_window.show()?;
+    // settings_window.show()?;
     slint::run_event_loop_until_quit()?;
     info!("应用正在退出...");
+    if let Err(e) = degrader_exit.stop() {
+        error!("退出时恢复网络失败: {}", e);
+    }
     Ok(())
 }
+
+/// 炉石进程当前的网络连接, 标出日志中记录的游戏服务器
+fn list_connections(pid: u32, server: Option<SocketAddrV4>) -> Vec<String> {
+    match network::get_process_by_pid(pid) {
+        Ok(connections) => connections
+            .iter()
+            .map(|info| {
+                let remote = SocketAddrV4::new(info.remote_addr_as_ipv4(), info.remote_port_as_u16());
+                if Some(remote) == server {
+                    format!("{} (游戏服务器)", info)
+                } else {
+                    info.to_string()
+                }
+            })
+            .collect(),
+        Err(e) => vec![format!("无法获取网络连接: {}", e)],
+    }
+}
@@ -1,12 +1,45 @@
 use anyhow::Result;
 use std::fmt;
 use std::net::Ipv4Addr;
-use windows::Win32::Foundation::{NO_ERROR, WIN32_ERROR};
+#[cfg(windows)]
+use windows::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR, WIN32_ERROR};
+#[cfg(windows)]
 use windows::Win32::NetworkManagement::IpHelper::{
     GetExtendedTcpTable, MIB_TCP_STATE_DELETE_TCB, MIB_TCPROW_LH, MIB_TCPROW_LH_0,
-    MIB_TCPTABLE_OWNER_MODULE, SetTcpEntry, TCP_TABLE_OWNER_MODULE_ALL,
+    MIB_TCPROW_OWNER_MODULE, MIB_TCPTABLE_OWNER_MODULE, SetTcpEntry, TCP_TABLE_OWNER_MODULE_ALL,
 };
+#[cfg(windows)]
+use windows::Win32::Networking::WinSock::AF_INET;
 
+/// `MIB_TCPTABLE_OWNER_MODULE` 中 `table` 字段的偏移: dwNumEntries 之后按行的 8 字节对齐填充
+#[cfg(any(windows, test))]
+const TCP_TABLE_HEADER_SIZE: usize = 8;
+/// `MIB_TCPROW_OWNER_MODULE` 的大小: 6 个 u32 + liCreateTimestamp(i64) + OwningModuleInfo([u64; 16])
+#[cfg(any(windows, test))]
+const TCP_ROW_SIZE: usize = 6 * 4 + 8 + 16 * 8;
+/// 行内 dwLocalAddr/dwLocalPort/dwRemoteAddr/dwRemotePort/dwOwningPid 的偏移
+#[cfg(any(windows, test))]
+const TCP_ROW_LOCAL_ADDR: usize = 4;
+#[cfg(any(windows, test))]
+const TCP_ROW_LOCAL_PORT: usize = 8;
+#[cfg(any(windows, test))]
+const TCP_ROW_REMOTE_ADDR: usize = 12;
+#[cfg(any(windows, test))]
+const TCP_ROW_REMOTE_PORT: usize = 16;
+#[cfg(any(windows, test))]
+const TCP_ROW_OWNING_PID: usize = 20;
+/// 两次调用之间连接表可能变大, 缓冲区不足时最多重试的次数
+#[cfg(windows)]
+const TCP_TABLE_MAX_RETRIES: usize = 5;
+
+#[cfg(windows)]
+const _: () = {
+    assert!(std::mem::size_of::<MIB_TCPROW_OWNER_MODULE>() == TCP_ROW_SIZE);
+    assert!(std::mem::offset_of!(MIB_TCPTABLE_OWNER_MODULE, table) == TCP_TABLE_HEADER_SIZE);
+    assert!(std::mem::offset_of!(MIB_TCPROW_OWNER_MODULE, dwOwningPid) == TCP_ROW_OWNING_PID);
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetworkInfo {
     pub local_addr: u32,
     pub local_port: u32,
@@ -53,65 +86,104 @@ impl fmt::Display for NetworkInfo {
     }
 }
 
+/// 获取指定进程的全部 IPv4 TCP 连接
+#[cfg(windows)]
 pub fn get_process_by_pid(pid: u32) -> Result<Vec<NetworkInfo>> {
     let mut size: u32 = 0;
-    let result: u32 = unsafe {
-        GetExtendedTcpTable(
-            None,
-            &mut size as *mut u32,
-            true,
-            2,
-            TCP_TABLE_OWNER_MODULE_ALL,
-            0,
-        )
-    };
-    anyhow::ensure!(
-        result == 122 || result == 0,
-        "GetExtendedTcpTable异常[1]: {:?}",
-        result
-    );
+    // 使用 u64 作为底层存储, 保证缓冲区满足表结构的 8 字节对齐
+    let mut buffer: Vec<u64> = Vec::new();
 
-    let mut buffer = vec![0u8; size as usize];
+    for _ in 0..TCP_TABLE_MAX_RETRIES {
+        let table = if buffer.is_empty() {
+            None
+        } else {
+            Some(buffer.as_mut_ptr() as *mut _)
+        };
+        let result = unsafe {
+            GetExtendedTcpTable(
+                table,
+                &mut size,
+                true,
+                AF_INET.0 as u32,
+                TCP_TABLE_OWNER_MODULE_ALL,
+                0,
+            )
+        };
+        match WIN32_ERROR(result) {
+            NO_ERROR => {
+                let capacity = buffer.len() * std::mem::size_of::<u64>();
+                let bytes =
+                    unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, capacity) };
+                return parse_tcp_table(&bytes[..(size as usize).min(capacity)], pid);
+            }
+            ERROR_INSUFFICIENT_BUFFER => {
+                buffer = vec![0u64; (size as usize).div_ceil(std::mem::size_of::<u64>())];
+            }
+            error => anyhow::bail!("GetExtendedTcpTable异常: {:?}", error),
+        }
+    }
 
-    let result = unsafe {
-        GetExtendedTcpTable(
-            Some(buffer.as_mut_ptr() as *mut _),
-            &mut size,
-            true,
-            2,
-            TCP_TABLE_OWNER_MODULE_ALL,
-            0,
-        )
-    };
+    anyhow::bail!(
+        "GetExtendedTcpTable异常: 连接表持续变化, 重试 {} 次后缓冲区仍然不足",
+        TCP_TABLE_MAX_RETRIES
+    )
+}
 
-    anyhow::ensure!(result == 0, "GetExtendedTcpTable异常[2]: {:?}", result);
+#[cfg(not(windows))]
+pub fn get_process_by_pid(_pid: u32) -> Result<Vec<NetworkInfo>> {
+    anyhow::bail!("当前平台不支持读取TCP连接表")
+}
 
-    buffer.truncate(size as usize);
+/// 解析 `GetExtendedTcpTable(TCP_TABLE_OWNER_MODULE_ALL)` 返回的原始缓冲区, 只保留属于 `pid` 的连接
+///
+/// 缓冲区不要求对齐, 所有字段均按偏移逐字节读取并做边界检查
+#[cfg(any(windows, test))]
+pub fn parse_tcp_table(buffer: &[u8], pid: u32) -> Result<Vec<NetworkInfo>> {
+    if buffer.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    let mut network_infos = Vec::new();
+    let num_entries = read_u32(buffer, 0)? as usize;
+    let rows_size = num_entries
+        .checked_mul(TCP_ROW_SIZE)
+        .and_then(|size| size.checked_add(TCP_TABLE_HEADER_SIZE))
+        .ok_or_else(|| anyhow::anyhow!("TCP连接表条目数异常: {}", num_entries))?;
+    anyhow::ensure!(
+        num_entries == 0 || buffer.len() >= rows_size,
+        "TCP连接表缓冲区过小: 需要 {} 字节, 实际 {} 字节",
+        rows_size,
+        buffer.len()
+    );
 
-    // 解析数据
-    if size > 0 {
-        let tcp_table = unsafe { &*(buffer.as_ptr() as *const MIB_TCPTABLE_OWNER_MODULE) };
-
-        let table_ptr = tcp_table.table.as_ptr();
-
-        for i in 0..tcp_table.dwNumEntries {
-            let entry = unsafe { &*table_ptr.add(i as usize) }; // 只处理匹配的PID
-            if entry.dwOwningPid == pid {
-                network_infos.push(NetworkInfo {
-                    local_addr: entry.dwLocalAddr,
-                    local_port: entry.dwLocalPort,
-                    remote_addr: entry.dwRemoteAddr,
-                    remote_port: entry.dwRemotePort,
-                });
-            }
+    let mut network_infos = Vec::new();
+    for i in 0..num_entries {
+        let row = TCP_TABLE_HEADER_SIZE + i * TCP_ROW_SIZE;
+        // 只处理匹配的PID
+        if read_u32(buffer, row + TCP_ROW_OWNING_PID)? != pid {
+            continue;
         }
+        network_infos.push(NetworkInfo {
+            local_addr: read_u32(buffer, row + TCP_ROW_LOCAL_ADDR)?,
+            local_port: read_u32(buffer, row + TCP_ROW_LOCAL_PORT)?,
+            remote_addr: read_u32(buffer, row + TCP_ROW_REMOTE_ADDR)?,
+            remote_port: read_u32(buffer, row + TCP_ROW_REMOTE_PORT)?,
+        });
     }
 
     Ok(network_infos)
 }
 
+/// 从缓冲区的 `offset` 处读取一个本机字节序的 u32
+#[cfg(any(windows, test))]
+fn read_u32(buffer: &[u8], offset: usize) -> Result<u32> {
+    let bytes = offset
+        .checked_add(4)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or_else(|| anyhow::anyhow!("TCP连接表越界读取: 偏移 {}", offset))?;
+    Ok(u32::from_ne_bytes(bytes.try_into()?))
+}
+
+#[cfg(windows)]
 pub fn close_tcp_connection(network_info: &NetworkInfo) -> Result<()> {
     unsafe {
         let tcp_row = MIB_TCPROW_LH {
@@ -131,3 +203,62 @@ pub fn close_tcp_connection(network_info: &NetworkInfo) -> Result<()> {
         }
     }
 }
+
+#[cfg(not(windows))]
+pub fn close_tcp_connection(_network_info: &NetworkInfo) -> Result<()> {
+    anyhow::bail!("当前平台不支持关闭TCP连接")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 `MIB_TCPTABLE_OWNER_MODULE` 的布局构造连接表, 每行为 (pid, 本地端口, 远程端口)
+    fn table(declared_rows: u32, rows: &[(u32, u32, u32)]) -> Vec<u8> {
+        let mut buffer = vec![0u8; TCP_TABLE_HEADER_SIZE + rows.len() * TCP_ROW_SIZE];
+        buffer[..4].copy_from_slice(&declared_rows.to_ne_bytes());
+        for (i, &(pid, local_port, remote_port)) in rows.iter().enumerate() {
+            let row = TCP_TABLE_HEADER_SIZE + i * TCP_ROW_SIZE;
+            let mut put = |offset: usize, value: u32| {
+                buffer[row + offset..row + offset + 4].copy_from_slice(&value.to_ne_bytes());
+            };
+            put(TCP_ROW_LOCAL_ADDR, 0x0100_007f);
+            put(TCP_ROW_LOCAL_PORT, local_port);
+            put(TCP_ROW_REMOTE_ADDR, 0x0403_0201);
+            put(TCP_ROW_REMOTE_PORT, remote_port);
+            put(TCP_ROW_OWNING_PID, pid);
+        }
+        buffer
+    }
+
+    #[test]
+    fn filters_rows_by_pid() {
+        let buffer = table(3, &[(10, 1, 2), (20, 3, 4), (10, 5, 6)]);
+        let infos = parse_tcp_table(&buffer, 10).unwrap();
+        assert_eq!(
+            infos.iter().map(|info| (info.local_port, info.remote_port)).collect::<Vec<_>>(),
+            [(1, 2), (5, 6)]
+        );
+        assert_eq!(infos[0].local_addr, 0x0100_007f);
+        assert_eq!(infos[0].remote_addr, 0x0403_0201);
+        assert!(parse_tcp_table(&buffer, 30).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        assert!(parse_tcp_table(&[1, 0, 0], 10).is_err());
+    }
+
+    #[test]
+    fn rejects_row_count_larger_than_buffer() {
+        let buffer = table(3, &[(10, 1, 2), (10, 3, 4)]);
+        assert!(parse_tcp_table(&buffer, 10).is_err());
+        assert!(parse_tcp_table(&table(u32::MAX, &[]), 10).is_err());
+    }
+
+    #[test]
+    fn accepts_empty_table() {
+        assert!(parse_tcp_table(&table(0, &[]), 10).unwrap().is_empty());
+        assert!(parse_tcp_table(&[], 10).unwrap().is_empty());
+    }
+}
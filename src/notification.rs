@@ -0,0 +1,72 @@
+use log::warn;
+use notify_rust::{Notification, Timeout};
+use std::time::Duration;
+
+use crate::config::NotificationsConfig;
+
+/// 通知的显示时长
+const NOTIFICATION_TIMEOUT: Timeout = Timeout::Milliseconds(4000);
+
+/// 需要通知用户的事件
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// 已断开连接, 附带拔线方式
+    DisconnectStarted(String),
+    /// 游戏已重新连接, 附带从断开到重连的耗时
+    Reconnected(Duration),
+    /// 拔线失败, 附带原因
+    Failed(String),
+    /// 本局拔线次数达到配置档的上限
+    CapReached(String),
+}
+
+impl Event {
+    fn enabled(&self, config: &NotificationsConfig) -> bool {
+        config.enabled
+            && match self {
+                Event::DisconnectStarted(_) => config.disconnect_started,
+                Event::Reconnected(_) => config.reconnected,
+                Event::Failed(_) => config.failed,
+                Event::CapReached(_) => config.cap_reached,
+            }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            Event::DisconnectStarted(_) => "已拔线",
+            Event::Reconnected(_) => "已重连",
+            Event::Failed(_) => "拔线失败",
+            Event::CapReached(_) => "已达拔线上限",
+        }
+    }
+
+    fn body(&self) -> String {
+        match self {
+            Event::DisconnectStarted(action) => format!("{}, 等待游戏重连...", action),
+            Event::Reconnected(duration) => {
+                format!("游戏已在 {:.1} 秒后重新连接。", duration.as_secs_f32())
+            }
+            Event::Failed(reason) | Event::CapReached(reason) => reason.clone(),
+        }
+    }
+}
+
+/// 按配置发送桌面通知(Windows 为系统通知, Linux 为 freedesktop 通知)
+///
+/// 在后台线程中发送, 不会阻塞调用方
+pub fn notify(config: &NotificationsConfig, event: Event) {
+    if !event.enabled(config) {
+        return;
+    }
+    std::thread::spawn(move || {
+        let result = Notification::new()
+            .appname("Hsarec")
+            .summary(event.summary())
+            .body(&event.body())
+            .timeout(NOTIFICATION_TIMEOUT)
+            .show();
+        if let Err(e) = result {
+            warn!("警告: 无法显示桌面通知 {:?}: {}", event, e);
+        }
+    });
+}
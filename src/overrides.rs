@@ -0,0 +1,300 @@
+use anyhow::Result;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// 环境变量前缀, 例如 `HSAREC_FOCUS_GUARD=true`, 嵌套的配置项用双下划线分隔: `HSAREC_DEGRADE__DELAY_MS=500`
+const ENV_PREFIX: &str = "HSAREC_";
+const ENV_SEPARATOR: &str = "__";
+
+/// 配置项的来源, 优先级从低到高: 默认值 < 配置文件 < 环境变量 < 命令行参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "默认值"),
+            Source::File => write!(f, "配置文件"),
+            Source::Env(name) => write!(f, "环境变量 {}", name),
+            Source::Cli => write!(f, "命令行参数"),
+        }
+    }
+}
+
+/// 覆盖单个配置项, `path` 为按 `.` 分隔的键, 例如 `degrade.delay_ms`
+#[derive(Debug, Clone)]
+struct Override {
+    path: Vec<String>,
+    value: toml::Value,
+    source: Source,
+}
+
+/// 叠加在配置文件之上的覆盖项, 按优先级从低到高排列, 只在内存中生效, 不会写回配置文件
+#[derive(Debug, Default)]
+pub struct Overrides {
+    items: Vec<Override>,
+    /// 无法解析的覆盖项, 其余覆盖项照常生效
+    errors: Vec<String>,
+}
+
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// 读取 `HSAREC_*` 环境变量和命令行的 `--set KEY=VALUE`, 需要在加载配置之前调用
+pub fn init(cli_sets: &[String]) -> &'static Overrides {
+    let overrides = Overrides::from_sources(std::env::vars(), cli_sets);
+    OVERRIDES.get_or_init(|| overrides)
+}
+
+/// 获取覆盖项, 未调用 `init` 时为空
+pub fn get() -> &'static Overrides {
+    OVERRIDES.get_or_init(Overrides::default)
+}
+
+impl Overrides {
+    /// 解析覆盖项, 格式错误的项记录在 `errors` 中并跳过
+    pub fn from_sources(
+        env: impl IntoIterator<Item = (String, String)>,
+        cli_sets: &[String],
+    ) -> Self {
+        let mut overrides = Overrides::default();
+
+        let mut env: Vec<_> = env
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name.len() > ENV_PREFIX.len())
+            .collect();
+        env.sort();
+        for (name, raw) in env {
+            let path: Vec<String> = name[ENV_PREFIX.len()..]
+                .to_lowercase()
+                .split(ENV_SEPARATOR)
+                .map(str::to_string)
+                .collect();
+            if path.iter().any(String::is_empty) {
+                overrides
+                    .errors
+                    .push(format!("无效的环境变量 {}, 配置项名称不能为空", name));
+                continue;
+            }
+            overrides.items.push(Override {
+                path,
+                value: parse_value(&raw),
+                source: Source::Env(name),
+            });
+        }
+
+        for set in cli_sets {
+            let Some((key, raw)) = set.split_once('=') else {
+                overrides
+                    .errors
+                    .push(format!("无效的 --set 参数 '{}', 格式应为 KEY=VALUE", set));
+                continue;
+            };
+            let path: Vec<String> = key.trim().split('.').map(str::to_string).collect();
+            if path.iter().any(String::is_empty) {
+                overrides
+                    .errors
+                    .push(format!("无效的 --set 参数 '{}', 配置项名称不能为空", set));
+                continue;
+            }
+            overrides.items.push(Override {
+                path,
+                value: parse_value(raw.trim()),
+                source: Source::Cli,
+            });
+        }
+        overrides
+    }
+
+    /// 格式错误而被跳过的覆盖项
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// 将覆盖项写入配置表
+    pub fn apply(&self, table: &mut toml::Table) {
+        for item in &self.items {
+            set_path(table, &item.path, item.value.clone());
+        }
+    }
+
+    /// 逐项写入配置表, 写入后 `check` 失败的覆盖项会被撤销
+    ///
+    /// 返回实际生效的覆盖项, 以及每个被跳过的覆盖项的错误说明
+    pub fn apply_checked(
+        &self,
+        table: &mut toml::Table,
+        mut check: impl FnMut(&toml::Table) -> Result<()>,
+    ) -> (Overrides, Vec<String>) {
+        let mut applied = Overrides::default();
+        let mut rejected = Vec::new();
+        for item in &self.items {
+            let mut candidate = table.clone();
+            set_path(&mut candidate, &item.path, item.value.clone());
+            match check(&candidate) {
+                Ok(()) => {
+                    *table = candidate;
+                    applied.items.push(item.clone());
+                }
+                Err(e) => rejected.push(format!(
+                    "{}中的配置项 {} = {} 无效, 已忽略: {}",
+                    item.source,
+                    item.path.join("."),
+                    item.value,
+                    e
+                )),
+            }
+        }
+        (applied, rejected)
+    }
+
+    /// 保存前调用: 被覆盖的配置项恢复为配置文件 `file` 中的原值, 文件中没有的则删除
+    pub fn restore(&self, table: &mut toml::Table, file: &toml::Table) {
+        for item in &self.items {
+            match get_path(file, &item.path) {
+                Some(value) => set_path(table, &item.path, value.clone()),
+                None => remove_path(table, &item.path),
+            }
+        }
+    }
+
+    /// 配置项 `path` 的最终来源, `file` 为(升级后的)配置文件内容
+    pub fn source_of(&self, path: &[String], file: &toml::Table) -> Source {
+        if let Some(item) = self.items.iter().rev().find(|item| item.path == path) {
+            return item.source.clone();
+        }
+        if get_path(file, path).is_some() {
+            Source::File
+        } else {
+            Source::Default
+        }
+    }
+}
+
+/// 按 TOML 语法解析值(`true`、`500`、`"text"` 等), 无法解析时当作字符串
+fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn get_path<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn set_path(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        let Some(next) = entry.as_table_mut() else {
+            return;
+        };
+        table = next;
+    }
+    table.insert(last.clone(), value);
+}
+
+fn remove_path(table: &mut toml::Table, path: &[String]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let Some(next) = table.get_mut(key).and_then(toml::Value::as_table_mut) else {
+            return;
+        };
+        table = next;
+    }
+    table.remove(last);
+}
+
+/// 展开配置表中的全部叶子配置项
+pub fn leaves(table: &toml::Table) -> Vec<(Vec<String>, &toml::Value)> {
+    let mut leaves = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(child) if !child.is_empty() => {
+                for (mut path, value) in self::leaves(child) {
+                    path.insert(0, key.clone());
+                    leaves.push((path, value));
+                }
+            }
+            _ => leaves.push((vec![key.clone()], value)),
+        }
+    }
+    leaves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_malformed_entries() {
+        let env = vec![("HSAREC_FOCUS_GUARD".to_string(), "true".to_string())];
+        let cli_sets = [
+            "missing_equals".to_string(),
+            ".delay_ms=1".to_string(),
+            "degrade.delay_ms=600".to_string(),
+        ];
+        let overrides = Overrides::from_sources(env, &cli_sets);
+        assert_eq!(overrides.errors().len(), 2);
+
+        let mut table = toml::Table::new();
+        overrides.apply(&mut table);
+        assert_eq!(table["focus_guard"], toml::Value::Boolean(true));
+        assert_eq!(table["degrade"]["delay_ms"], toml::Value::Integer(600));
+    }
+
+    #[test]
+    fn apply_checked_keeps_valid_entries() {
+        let cli_sets = [
+            "delay_ms=\"slow\"".to_string(),
+            "loss_percent=5".to_string(),
+        ];
+        let overrides = Overrides::from_sources(Vec::new(), &cli_sets);
+        let mut table = toml::Table::new();
+        let (applied, rejected) = overrides.apply_checked(&mut table, |table| {
+            anyhow::ensure!(
+                table.values().all(toml::Value::is_integer),
+                "必须是整数"
+            );
+            Ok(())
+        });
+
+        assert_eq!(rejected.len(), 1);
+        assert!(rejected[0].contains("delay_ms"));
+        assert!(!table.contains_key("delay_ms"));
+        assert_eq!(table["loss_percent"], toml::Value::Integer(5));
+        assert_eq!(
+            applied.source_of(&["loss_percent".to_string()], &toml::Table::new()),
+            Source::Cli
+        );
+        assert_eq!(
+            applied.source_of(&["delay_ms".to_string()], &toml::Table::new()),
+            Source::Default
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// 程序目录下存在该文件时使用便携模式: 配置和日志都放在程序目录
+const PORTABLE_MARKER: &str = "portable";
+const APP_DIR_NAME: &str = "hsarec";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const LOG_FILE_NAME: &str = "hsarec.log";
+
+/// 配置文件和日志文件的位置
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    pub config_file: PathBuf,
+    pub log_file: PathBuf,
+    /// 配置和日志放在程序目录(或 `--config` 指定的目录)而不是系统目录
+    pub portable: bool,
+    /// 配置文件由 `--config` 指定, 开机启动时需要带上同样的参数
+    pub config_from_cli: bool,
+}
+
+static PATHS: OnceLock<AppPaths> = OnceLock::new();
+
+/// 根据命令行参数确定文件位置, 需要在初始化日志和加载配置之前调用
+///
+/// 优先级: `--config` 指定的文件 > 程序目录下的 `portable` 标记 > 系统目录
+/// (Linux 下为 XDG 目录, Windows 下为 `%APPDATA%`/`%LOCALAPPDATA%`)
+pub fn init(config_override: Option<PathBuf>) -> Result<&'static AppPaths> {
+    let paths = resolve(config_override)?;
+    Ok(PATHS.get_or_init(|| paths))
+}
+
+/// 获取已确定的文件位置, 未调用 `init` 时按默认规则确定
+pub fn get() -> &'static AppPaths {
+    PATHS.get_or_init(|| {
+        resolve(None).unwrap_or_else(|_| AppPaths {
+            config_file: PathBuf::from(CONFIG_FILE_NAME),
+            log_file: PathBuf::from(LOG_FILE_NAME),
+            portable: true,
+            config_from_cli: false,
+        })
+    })
+}
+
+fn exe_dir() -> Result<PathBuf> {
+    let current_exe = std::env::current_exe()?;
+    current_exe
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| anyhow::anyhow!("无法获取当前可执行文件的父目录"))
+}
+
+fn resolve(config_override: Option<PathBuf>) -> Result<AppPaths> {
+    if let Some(config_file) = config_override {
+        let dir = config_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        return Ok(AppPaths {
+            log_file: dir.join(LOG_FILE_NAME),
+            config_file,
+            portable: true,
+            config_from_cli: true,
+        });
+    }
+
+    let exe_dir = exe_dir()?;
+    let portable = AppPaths {
+        config_file: exe_dir.join(CONFIG_FILE_NAME),
+        log_file: exe_dir.join(LOG_FILE_NAME),
+        portable: true,
+        config_from_cli: false,
+    };
+    if exe_dir.join(PORTABLE_MARKER).exists() {
+        return Ok(portable);
+    }
+    match (dirs::config_dir(), dirs::data_local_dir()) {
+        (Some(config_dir), Some(data_dir)) => Ok(AppPaths {
+            config_file: config_dir.join(APP_DIR_NAME).join(CONFIG_FILE_NAME),
+            log_file: data_dir.join(APP_DIR_NAME).join(LOG_FILE_NAME),
+            portable: false,
+            config_from_cli: false,
+        }),
+        // 找不到系统目录时退回到程序目录
+        _ => Ok(portable),
+    }
+}
+
+/// 单实例锁等运行时文件所在的目录, 与配置文件的位置无关, 同一用户的便携版和安装版共用
+///
+/// Linux 下为 `$XDG_RUNTIME_DIR/hsarec`, 没有该目录时和 Windows 一样使用本地数据目录
+pub fn runtime_dir() -> Result<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .ok_or_else(|| anyhow::anyhow!("无法确定运行时目录"))
+}
+
+/// 首次使用系统目录时, 把程序目录下旧的配置文件复制过去, 避免升级后丢失配置
+pub fn migrate_legacy_config() {
+    let paths = get();
+    if paths.portable || paths.config_file.exists() {
+        return;
+    }
+    let Ok(legacy) = exe_dir().map(|dir| dir.join(CONFIG_FILE_NAME)) else {
+        return;
+    };
+    if !legacy.exists() {
+        return;
+    }
+    let result = paths
+        .config_file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::copy(&legacy, &paths.config_file));
+    match result {
+        Ok(_) => info!(
+            "已将程序目录下的配置文件 {:?} 复制到 {:?}",
+            legacy, paths.config_file
+        ),
+        Err(e) => warn!("警告: 无法复制旧的配置文件 {:?}: {}", legacy, e),
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::config::Profile;
+
+/// 拔线请求被拒绝的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// 本局拔线次数达到上限
+    CapReached { count: u32, max: u32 },
+    /// 距上次拔线未满冷却时间, 附带剩余秒数(向上取整)
+    Cooldown { remaining_secs: u64 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::CapReached { count, max } => {
+                write!(f, "本局已拔线 {} 次, 达到上限 {} 次。", count, max)
+            }
+            Rejection::Cooldown { remaining_secs } => {
+                write!(f, "冷却中, 请在 {} 秒后重试。", remaining_secs)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// 按当前配置档限制拔线: 两次拔线的冷却时间以及每局的次数上限
+#[derive(Debug, Default)]
+pub struct DisconnectPolicy {
+    last: Option<Instant>,
+    count: u32,
+}
+
+impl DisconnectPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 新的一局开始, 重新计数
+    pub fn new_game(&mut self) {
+        self.count = 0;
+    }
+
+    /// 本局已经拔线的次数
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// 检查此时是否允许拔线, 不允许时返回原因
+    pub fn check(&self, profile: &Profile, now: Instant) -> Result<(), Rejection> {
+        if profile.max_per_game > 0 && self.count >= profile.max_per_game {
+            return Err(Rejection::CapReached {
+                count: self.count,
+                max: profile.max_per_game,
+            });
+        }
+        let cooldown = Duration::from_secs(profile.cooldown_secs);
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last);
+            if elapsed < cooldown {
+                return Err(Rejection::Cooldown {
+                    remaining_secs: (cooldown - elapsed).as_secs_f64().ceil() as u64,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录一次成功的拔线
+    pub fn record(&mut self, now: Instant) {
+        self.last = Some(now);
+        self.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DisconnectStrategy;
+
+    fn limits(cooldown_secs: u64, max_per_game: u32) -> Profile {
+        Profile {
+            strategy: DisconnectStrategy::Server,
+            cooldown_secs,
+            max_per_game,
+        }
+    }
+
+    #[test]
+    fn cooldown_ends_exactly_after_configured_time() {
+        let profile = limits(30, 0);
+        let start = Instant::now();
+        let mut policy = DisconnectPolicy::new();
+        assert_eq!(policy.check(&profile, start), Ok(()));
+        policy.record(start);
+
+        assert_eq!(
+            policy.check(&profile, start),
+            Err(Rejection::Cooldown { remaining_secs: 30 })
+        );
+        assert_eq!(
+            policy.check(&profile, start + Duration::from_millis(29_500)),
+            Err(Rejection::Cooldown { remaining_secs: 1 })
+        );
+        assert_eq!(policy.check(&profile, start + Duration::from_secs(30)), Ok(()));
+        // 冷却时间为 0 时不限制
+        assert_eq!(policy.check(&limits(0, 0), start), Ok(()));
+    }
+
+    #[test]
+    fn cap_is_reached_after_max_disconnects() {
+        let profile = limits(0, 2);
+        let now = Instant::now();
+        let mut policy = DisconnectPolicy::new();
+        policy.record(now);
+        assert_eq!(policy.check(&profile, now), Ok(()));
+        policy.record(now);
+        assert_eq!(policy.count(), 2);
+        assert_eq!(
+            policy.check(&profile, now),
+            Err(Rejection::CapReached { count: 2, max: 2 })
+        );
+        // 上限为 0 时不限制
+        assert_eq!(policy.check(&limits(0, 0), now), Ok(()));
+    }
+
+    #[test]
+    fn new_game_resets_count_but_not_cooldown() {
+        let start = Instant::now();
+        let mut policy = DisconnectPolicy::new();
+        policy.record(start);
+        assert!(policy.check(&limits(0, 1), start).is_err());
+
+        policy.new_game();
+        assert_eq!(policy.count(), 0);
+        assert_eq!(policy.check(&limits(0, 1), start), Ok(()));
+        assert_eq!(
+            policy.check(&limits(10, 1), start),
+            Err(Rejection::Cooldown { remaining_secs: 10 })
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::tray::TrayIcon;
+
+/// 断开后超过该时间仍未重连, 视为卡住
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+/// 拔线失败后错误图标的显示时长
+const ERROR_DISPLAY: Duration = Duration::from_secs(10);
+
+/// 拔线流程的状态, 决定托盘图标和状态窗口中的"拔线状态"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ReconnectState {
+    /// 未检测到炉石进程
+    #[default]
+    NoGame,
+    Idle,
+    /// 正在关闭连接
+    Disconnecting,
+    /// 已断开, 等待游戏重连
+    Waiting { since: Instant, timed_out: bool },
+    /// 拔线失败
+    Error { reason: String, since: Instant },
+}
+
+impl ReconnectState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn settled(game_running: bool) -> Self {
+        if game_running {
+            ReconnectState::Idle
+        } else {
+            ReconnectState::NoGame
+        }
+    }
+
+    /// 开始关闭连接
+    pub fn start(&mut self) {
+        *self = ReconnectState::Disconnecting;
+    }
+
+    /// 连接已关闭, 开始等待重连
+    pub fn disconnected(&mut self, now: Instant) {
+        *self = ReconnectState::Waiting {
+            since: now,
+            timed_out: false,
+        };
+    }
+
+    pub fn failed(&mut self, reason: String, now: Instant) {
+        *self = ReconnectState::Error { reason, since: now };
+    }
+
+    /// 日志中出现新的游戏服务器, 返回这是否是拔线后的重连
+    pub fn reconnected(&mut self) -> bool {
+        let was_waiting = self.is_waiting();
+        *self = ReconnectState::Idle;
+        was_waiting
+    }
+
+    /// 按游戏进程是否存在和经过的时间更新状态
+    pub fn tick(&mut self, game_running: bool, now: Instant) {
+        match self {
+            ReconnectState::Disconnecting => {}
+            ReconnectState::Waiting { .. } if !game_running => {
+                *self = ReconnectState::NoGame;
+            }
+            ReconnectState::Waiting { since, timed_out } => {
+                if now.saturating_duration_since(*since) >= RECONNECT_TIMEOUT {
+                    *timed_out = true;
+                }
+            }
+            ReconnectState::Error { since, .. } => {
+                if now.saturating_duration_since(*since) >= ERROR_DISPLAY {
+                    *self = Self::settled(game_running);
+                }
+            }
+            ReconnectState::NoGame | ReconnectState::Idle => {
+                *self = Self::settled(game_running);
+            }
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        matches!(self, ReconnectState::Waiting { .. })
+    }
+
+    pub fn icon(&self) -> TrayIcon {
+        match self {
+            ReconnectState::NoGame => TrayIcon::NoGame,
+            ReconnectState::Idle => TrayIcon::Idle,
+            ReconnectState::Disconnecting => TrayIcon::Disconnecting,
+            ReconnectState::Waiting {
+                timed_out: false, ..
+            } => TrayIcon::Waiting,
+            ReconnectState::Waiting { timed_out: true, .. } | ReconnectState::Error { .. } => {
+                TrayIcon::Error
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReconnectState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconnectState::NoGame => write!(f, "未检测到炉石"),
+            ReconnectState::Idle => write!(f, "空闲"),
+            ReconnectState::Disconnecting => write!(f, "正在断开"),
+            ReconnectState::Waiting {
+                timed_out: false, ..
+            } => write!(f, "等待重连"),
+            ReconnectState::Waiting { timed_out: true, .. } => {
+                write!(f, "等待重连超时, 可以再次拔线")
+            }
+            ReconnectState::Error { reason, .. } => write!(f, "拔线失败: {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiting(since: Instant) -> ReconnectState {
+        let mut state = ReconnectState::new();
+        state.start();
+        state.disconnected(since);
+        state
+    }
+
+    #[test]
+    fn waiting_times_out() {
+        let start = Instant::now();
+        let mut state = waiting(start);
+        state.tick(true, start + RECONNECT_TIMEOUT - Duration::from_secs(1));
+        assert_eq!(state.icon(), TrayIcon::Waiting);
+
+        state.tick(true, start + RECONNECT_TIMEOUT);
+        assert_eq!(
+            state,
+            ReconnectState::Waiting {
+                since: start,
+                timed_out: true
+            }
+        );
+        assert_eq!(state.icon(), TrayIcon::Error);
+    }
+
+    #[test]
+    fn waiting_ends_when_game_exits() {
+        let start = Instant::now();
+        let mut state = waiting(start);
+        state.tick(false, start);
+        assert_eq!(state, ReconnectState::NoGame);
+        assert!(!state.reconnected());
+    }
+
+    #[test]
+    fn error_settles_after_display_time() {
+        let start = Instant::now();
+        let mut state = ReconnectState::new();
+        state.failed("拒绝访问".to_string(), start);
+        state.tick(true, start + ERROR_DISPLAY - Duration::from_secs(1));
+        assert_eq!(state.icon(), TrayIcon::Error);
+
+        state.tick(true, start + ERROR_DISPLAY);
+        assert_eq!(state, ReconnectState::Idle);
+    }
+
+    #[test]
+    fn disconnecting_ignores_ticks() {
+        let mut state = ReconnectState::new();
+        state.start();
+        state.tick(false, Instant::now());
+        assert_eq!(state.icon(), TrayIcon::Disconnecting);
+    }
+
+    #[test]
+    fn reconnected_reports_whether_it_was_waiting() {
+        let start = Instant::now();
+        let mut state = waiting(start);
+        assert!(state.reconnected());
+        assert_eq!(state, ReconnectState::Idle);
+        assert!(!state.reconnected());
+
+        let mut state = waiting(start);
+        state.tick(true, start + RECONNECT_TIMEOUT);
+        assert!(state.reconnected());
+
+        let mut state = ReconnectState::new();
+        state.failed("拒绝访问".to_string(), start);
+        assert!(!state.reconnected());
+        assert_eq!(state, ReconnectState::Idle);
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddrV4;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// 拔线历史最多保留的条数
+const MAX_HISTORY: usize = 50;
+
+/// 启动时记录的本地时区偏移, Linux 上创建其他线程之后无法再查询本地时区
+static LOCAL_OFFSET: OnceLock<time::UtcOffset> = OnceLock::new();
+
+/// 记录本地时区偏移, 需要在创建其他线程之前调用, 无法获取时使用 UTC
+pub fn init_local_offset() {
+    let offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    let _ = LOCAL_OFFSET.set(offset);
+}
+
+/// 一次拔线的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// 已断开, 等待游戏重连
+    Pending,
+    /// 游戏已重新连接, 附带从断开到重连的耗时
+    Reconnected(Duration),
+    /// 断开失败
+    Failed(String),
+    /// 被配置档的冷却时间或次数上限拒绝
+    Rejected(String),
+    /// 重连之前游戏已退出
+    GameExited,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pending => write!(f, "等待重连"),
+            Outcome::Reconnected(_) => write!(f, "已重连"),
+            Outcome::Failed(reason) => write!(f, "失败: {}", reason),
+            Outcome::Rejected(reason) => write!(f, "已忽略: {}", reason),
+            Outcome::GameExited => write!(f, "游戏已退出"),
+        }
+    }
+}
+
+/// 一条拔线记录
+#[derive(Debug, Clone)]
+pub struct DisconnectRecord {
+    /// 发起拔线的本地时间, 例如 `21:03:15`
+    pub time: String,
+    /// 拔线方式, 例如 "断开游戏服务器"
+    pub action: String,
+    pub outcome: Outcome,
+    started: Instant,
+}
+
+impl DisconnectRecord {
+    /// 显示用的耗时, 等待重连时为已等待的时间
+    pub fn duration(&self) -> Option<Duration> {
+        match self.outcome {
+            Outcome::Pending => Some(self.started.elapsed()),
+            Outcome::Reconnected(duration) => Some(duration),
+            Outcome::Failed(_) | Outcome::Rejected(_) | Outcome::GameExited => None,
+        }
+    }
+}
+
+/// 拔线历史, 最新的记录在前
+#[derive(Debug, Default)]
+pub struct History {
+    records: VecDeque<DisconnectRecord>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次拔线请求
+    pub fn push(&mut self, action: &str, outcome: Outcome, now: Instant) {
+        self.records.push_front(DisconnectRecord {
+            time: local_time(),
+            action: action.to_string(),
+            outcome,
+            started: now,
+        });
+        self.records.truncate(MAX_HISTORY);
+    }
+
+    /// 游戏重新连接后, 结束全部等待中的拔线, 返回最近一次拔线从断开到重连的耗时
+    pub fn reconnected(&mut self, now: Instant) -> Option<Duration> {
+        let mut newest = None;
+        for record in self.pending() {
+            let duration = now.saturating_duration_since(record.started);
+            record.outcome = Outcome::Reconnected(duration);
+            newest.get_or_insert(duration);
+        }
+        newest
+    }
+
+    /// 游戏退出后, 结束全部等待中的拔线
+    pub fn game_exited(&mut self) {
+        for record in self.pending() {
+            record.outcome = Outcome::GameExited;
+        }
+    }
+
+    fn pending(&mut self) -> impl Iterator<Item = &mut DisconnectRecord> {
+        self.records
+            .iter_mut()
+            .filter(|record| record.outcome == Outcome::Pending)
+    }
+
+    pub fn records(&self) -> Vec<DisconnectRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+/// 状态窗口展示的内容
+#[derive(Debug, Clone)]
+pub struct StatusSnapshot {
+    /// 炉石进程 PID, 未找到时为错误信息
+    pub pid: Result<u32, String>,
+    /// 日志中记录的当前游戏服务器
+    pub server: Option<SocketAddrV4>,
+    /// 炉石进程当前的网络连接
+    pub connections: Vec<String>,
+    /// 当前拔线状态
+    pub state: String,
+    /// 降级模式、焦点保护不可用等警告, 每行一条
+    pub warning: Option<String>,
+    pub history: Vec<DisconnectRecord>,
+}
+
+/// 当前本地时间 `时:分:秒`, 未记录本地时区时使用 UTC
+fn local_time() -> String {
+    let offset = LOCAL_OFFSET.get().copied().unwrap_or(time::UtcOffset::UTC);
+    let now = time::OffsetDateTime::now_utc().to_offset(offset);
+    format!("{:02}:{:02}:{:02}", now.hour(), now.minute(), now.second())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_closes_every_pending_record() {
+        let mut history = History::new();
+        let start = Instant::now();
+        history.push("断开游戏服务器", Outcome::Pending, start);
+        history.push("断开游戏服务器", Outcome::Failed("超时".to_string()), start);
+        history.push("断开全部连接", Outcome::Pending, start + Duration::from_secs(2));
+
+        let duration = history.reconnected(start + Duration::from_secs(5));
+        assert_eq!(duration, Some(Duration::from_secs(3)));
+        let outcomes: Vec<Outcome> = history.records().into_iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Reconnected(Duration::from_secs(3)),
+                Outcome::Failed("超时".to_string()),
+                Outcome::Reconnected(Duration::from_secs(5)),
+            ]
+        );
+        assert_eq!(history.reconnected(start + Duration::from_secs(6)), None);
+    }
+
+    #[test]
+    fn game_exit_closes_pending_records() {
+        let mut history = History::new();
+        let start = Instant::now();
+        history.push("断开游戏服务器", Outcome::Pending, start);
+        history.push("断开游戏服务器", Outcome::Pending, start);
+        history.game_exited();
+
+        assert!(history.records().iter().all(|r| r.outcome == Outcome::GameExited));
+        assert_eq!(history.reconnected(start), None);
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+/// 来自系统随机数的十六进制令牌
+pub fn generate(bytes: usize) -> Result<String> {
+    let mut buf = vec![0u8; bytes];
+    getrandom::fill(&mut buf).map_err(|e| anyhow::anyhow!("无法生成随机令牌: {}", e))?;
+    Ok(buf.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 比较令牌, 耗时与第一个不同字符的位置无关
+pub fn matches(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_random() {
+        let token = generate(32).unwrap();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate(32).unwrap());
+        assert!(matches(&token, &token.clone()));
+        assert!(!matches(&token, &token[1..]));
+    }
+}
@@ -1,32 +1,506 @@
 use anyhow::Result;
 use flume::Sender;
-use tray_item::{IconSource, TrayItem};
+use std::net::SocketAddrV4;
+use std::sync::Arc;
+
+use crate::config::{Config, DisconnectStrategy};
+use crate::hotkey::{HotkeyAction, HotkeySequence};
+
+mod noop;
+#[cfg(target_os = "linux")]
+mod sni;
+#[cfg(windows)]
+mod win32;
+
+pub use noop::NoopBackend;
 
 #[derive(Debug, Clone)]
 pub enum TrayMessage {
     Setting,
     Reconnect,
+    ReconnectAll,
+    Degrade,
+    ToggleAutoStart,
+    ToggleExitWithGame,
+    ShowStatus,
+    /// 打开状态窗口
+    StatusWindow,
+    SelectProfile(String),
+    /// 修改当前配置档的拔线方式
+    SetStrategy(DisconnectStrategy),
+    /// 降级模式下以管理员权限重新启动
+    RestartAsAdmin,
     Exit,
-    UpdateMenu(String),
-}
-
-pub fn setup_tray(tx: Sender<TrayMessage>, reconnect_hotkey: &str) -> Result<TrayItem> {
-    let reconnect_name = format!("开始拔线({})", reconnect_hotkey);
-    let mut tray = TrayItem::new("Hsarec", IconSource::Resource("#1"))?;
-    let tx_clone = tx.clone();
-    tray.add_menu_item(&reconnect_name, move || {
-        let _ = tx_clone.send(TrayMessage::Reconnect);
-    })?;
-    tray.inner_mut().add_separator()?;
-    let tx_setting = tx.clone();
-    tray.add_menu_item("设置快捷键", move || {
-        let _ = tx_setting.send(TrayMessage::Setting);
-    })?;
-    tray.add_menu_item("关于我", move || {
-        let _ = webbrowser::open("https://blog.3gxk.net/about.html");
-    })?;
-    tray.add_menu_item("退出程序", move || {
-        let _ = tx.send(TrayMessage::Exit);
-    })?;
-    Ok(tray)
+    UpdateMenu(Config),
+}
+
+/// 托盘菜单中只读展示的运行状态
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayStatus {
+    /// 检测到的炉石进程 PID
+    pub pid: Option<u32>,
+    /// 日志中记录的当前游戏服务器
+    pub server: Option<SocketAddrV4>,
+    /// 本局已拔线次数
+    pub disconnects: u32,
+}
+
+/// 托盘图标, 对应拔线流程的各个状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrayIcon {
+    Idle,
+    /// 未检测到炉石进程
+    NoGame,
+    /// 正在关闭连接
+    Disconnecting,
+    /// 已断开, 等待游戏重连
+    Waiting,
+    /// 拔线失败或重连超时
+    Error,
+}
+
+pub type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// 托盘的底层实现
+///
+/// 菜单项按添加顺序排列, 返回的 id 用于之后修改文字
+pub trait TrayBackend: Send {
+    fn name(&self) -> &'static str;
+    /// 添加不可点击的文字
+    fn add_label(&mut self, text: &str) -> Result<u32>;
+    fn set_label(&mut self, id: u32, text: &str) -> Result<()>;
+    fn add_item(&mut self, text: &str, callback: Callback) -> Result<u32>;
+    fn set_item_label(&mut self, id: u32, text: &str) -> Result<()>;
+    fn add_separator(&mut self) -> Result<()>;
+    /// 开始名为 `title` 的子菜单, 之后添加的菜单项都放入其中, 直到调用 `end_submenu`, 不支持嵌套
+    fn begin_submenu(&mut self, title: &str) -> Result<()>;
+    fn end_submenu(&mut self) -> Result<()>;
+    /// 清空菜单, 之前返回的 id 全部失效
+    fn clear(&mut self) -> Result<()>;
+    fn set_icon(&mut self, icon: TrayIcon) -> Result<()>;
+}
+
+/// 根据当前平台选择托盘实现, 没有图形会话或不支持的平台使用不显示任何内容的实现
+pub fn default_backend() -> Result<Box<dyn TrayBackend>> {
+    #[cfg(windows)]
+    {
+        Ok(Box::new(win32::Win32Backend::new()?))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+            log::warn!("警告: 未找到 D-Bus 会话, 不显示托盘图标");
+            return Ok(Box::new(NoopBackend::new()));
+        }
+        Ok(Box::new(sni::SniBackend::new()?))
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Ok(Box::new(NoopBackend::new()))
+    }
+}
+
+/// 菜单项名称, 绑定了热键时在后面附上规范化后的热键或按键序列
+fn menu_label(name: &str, config: &Config, action: HotkeyAction) -> String {
+    match config.hotkey_for(action) {
+        Some(hotkey) => match hotkey.parse::<HotkeySequence>() {
+            Ok(sequence) => format!("{}({})", name, sequence),
+            Err(_) => format!("{}({})", name, hotkey),
+        },
+        None => name.to_string(),
+    }
+}
+
+/// 分组中的选项, 选中的项前面带勾
+fn option_label(name: &str, checked: bool) -> String {
+    if checked {
+        format!("    ✓ {}", name)
+    } else {
+        format!("       {}", name)
+    }
+}
+
+/// 随配置变化的菜单项名称, 与 `Tray` 中记录的菜单项 id 一一对应
+struct ConfigLabels {
+    reconnect: String,
+    reconnect_all: String,
+    degrade: String,
+    strategy_server: String,
+    strategy_all: String,
+    profiles: Vec<String>,
+    auto_start: String,
+    exit_with_game: String,
+}
+
+impl ConfigLabels {
+    fn new(config: &Config, degraded: bool) -> Self {
+        let strategy = config.active_profile().strategy;
+        let unavailable = if degraded { " (不可用)" } else { "" };
+        ConfigLabels {
+            reconnect: menu_label("开始拔线", config, HotkeyAction::Disconnect) + unavailable,
+            reconnect_all: menu_label("断开全部连接", config, HotkeyAction::DisconnectAll)
+                + unavailable,
+            degrade: menu_label("弱网模拟", config, HotkeyAction::Degrade) + unavailable,
+            strategy_server: option_label(
+                "只断开游戏服务器",
+                strategy == DisconnectStrategy::Server,
+            ),
+            strategy_all: option_label("断开全部连接", strategy == DisconnectStrategy::All),
+            profiles: config
+                .profiles
+                .keys()
+                .map(|name| option_label(name, *name == config.profile))
+                .collect(),
+            auto_start: option_label("开机自动启动", config.auto_start),
+            exit_with_game: option_label("炉石退出时自动退出", config.exit_with_game),
+        }
+    }
+}
+
+/// 托盘图标及其菜单
+///
+/// 拔线方式和配置档放在子菜单中。
+/// 配置或状态变化时只修改菜单项文字, 配置档增删时才重建整个菜单。
+/// 降级模式(没有管理员权限, 无法拔线)下在顶部显示警告和重新启动的菜单项
+pub struct Tray {
+    backend: Box<dyn TrayBackend>,
+    tx: Sender<TrayMessage>,
+    degraded: bool,
+    profile_names: Vec<String>,
+    game_label: u32,
+    server_label: u32,
+    count_label: u32,
+    reconnect_item: u32,
+    reconnect_all_item: u32,
+    degrade_item: u32,
+    strategy_server_item: u32,
+    strategy_all_item: u32,
+    profile_items: Vec<u32>,
+    auto_start_item: u32,
+    exit_with_game_item: u32,
+    status: TrayStatus,
+    icon: TrayIcon,
+}
+
+/// 点击后发送 `msg` 的菜单回调
+fn send(tx: &Sender<TrayMessage>, msg: TrayMessage) -> Callback {
+    let tx = tx.clone();
+    Arc::new(move || {
+        let _ = tx.send(msg.clone());
+    })
+}
+
+impl Tray {
+    pub fn new(
+        backend: Box<dyn TrayBackend>,
+        tx: Sender<TrayMessage>,
+        config: &Config,
+        status: &TrayStatus,
+        degraded: bool,
+    ) -> Result<Self> {
+        let mut tray = Tray {
+            backend,
+            tx,
+            degraded,
+            profile_names: Vec::new(),
+            game_label: 0,
+            server_label: 0,
+            count_label: 0,
+            reconnect_item: 0,
+            reconnect_all_item: 0,
+            degrade_item: 0,
+            strategy_server_item: 0,
+            strategy_all_item: 0,
+            profile_items: Vec::new(),
+            auto_start_item: 0,
+            exit_with_game_item: 0,
+            status: status.clone(),
+            icon: TrayIcon::Idle,
+        };
+        tray.build(config)?;
+        Ok(tray)
+    }
+
+    /// 按配置构建整个菜单
+    fn build(&mut self, config: &Config) -> Result<()> {
+        let labels = ConfigLabels::new(config, self.degraded);
+        let tx = &self.tx;
+        let menu = &mut self.backend;
+
+        if self.degraded {
+            menu.add_label("⚠ 未获得管理员权限, 无法拔线")?;
+            menu.add_item("以管理员身份重新启动", send(tx, TrayMessage::RestartAsAdmin))?;
+            menu.add_separator()?;
+        }
+
+        self.game_label = menu.add_label(&Self::game_text(&self.status))?;
+        self.server_label = menu.add_label(&Self::server_text(&self.status))?;
+        self.count_label = menu.add_label(&Self::count_text(&self.status))?;
+        menu.add_separator()?;
+
+        self.reconnect_item = menu.add_item(&labels.reconnect, send(tx, TrayMessage::Reconnect))?;
+        self.reconnect_all_item =
+            menu.add_item(&labels.reconnect_all, send(tx, TrayMessage::ReconnectAll))?;
+        self.degrade_item = menu.add_item(&labels.degrade, send(tx, TrayMessage::Degrade))?;
+        menu.add_separator()?;
+
+        menu.begin_submenu("拔线方式")?;
+        self.strategy_server_item = menu.add_item(
+            &labels.strategy_server,
+            send(tx, TrayMessage::SetStrategy(DisconnectStrategy::Server)),
+        )?;
+        self.strategy_all_item = menu.add_item(
+            &labels.strategy_all,
+            send(tx, TrayMessage::SetStrategy(DisconnectStrategy::All)),
+        )?;
+        menu.end_submenu()?;
+        menu.begin_submenu("配置档")?;
+        self.profile_items.clear();
+        for (name, label) in config.profiles.keys().zip(&labels.profiles) {
+            self.profile_items.push(
+                menu.add_item(label, send(tx, TrayMessage::SelectProfile(name.clone())))?,
+            );
+        }
+        menu.end_submenu()?;
+        menu.add_separator()?;
+
+        self.auto_start_item =
+            menu.add_item(&labels.auto_start, send(tx, TrayMessage::ToggleAutoStart))?;
+        self.exit_with_game_item =
+            menu.add_item(&labels.exit_with_game, send(tx, TrayMessage::ToggleExitWithGame))?;
+        menu.add_separator()?;
+
+        menu.add_item("状态窗口", send(tx, TrayMessage::StatusWindow))?;
+        menu.add_item("设置", send(tx, TrayMessage::Setting))?;
+        menu.add_item(
+            "关于我",
+            Arc::new(|| {
+                let _ = webbrowser::open("https://blog.3gxk.net/about.html");
+            }),
+        )?;
+        menu.add_item("退出程序", send(tx, TrayMessage::Exit))?;
+
+        self.profile_names = config.profiles.keys().cloned().collect();
+        Ok(())
+    }
+
+    fn game_text(status: &TrayStatus) -> String {
+        match status.pid {
+            Some(pid) => format!("炉石: 已检测到 (PID {})", pid),
+            None => "炉石: 未检测到".to_string(),
+        }
+    }
+
+    fn server_text(status: &TrayStatus) -> String {
+        match status.server {
+            Some(server) => format!("服务器: {}", server),
+            None => "服务器: 未检测到".to_string(),
+        }
+    }
+
+    fn count_text(status: &TrayStatus) -> String {
+        format!("本局拔线: {} 次", status.disconnects)
+    }
+
+    /// 按新配置更新菜单项文字, 配置档增删时重建菜单
+    pub fn update_config(&mut self, config: &Config) -> Result<()> {
+        if !config.profiles.keys().eq(self.profile_names.iter()) {
+            self.backend.clear()?;
+            return self.build(config);
+        }
+        let labels = ConfigLabels::new(config, self.degraded);
+        let menu = &mut self.backend;
+        menu.set_item_label(self.reconnect_item, &labels.reconnect)?;
+        menu.set_item_label(self.reconnect_all_item, &labels.reconnect_all)?;
+        menu.set_item_label(self.degrade_item, &labels.degrade)?;
+        menu.set_item_label(self.strategy_server_item, &labels.strategy_server)?;
+        menu.set_item_label(self.strategy_all_item, &labels.strategy_all)?;
+        for (id, label) in self.profile_items.iter().zip(&labels.profiles) {
+            menu.set_item_label(*id, label)?;
+        }
+        menu.set_item_label(self.auto_start_item, &labels.auto_start)?;
+        menu.set_item_label(self.exit_with_game_item, &labels.exit_with_game)?;
+        Ok(())
+    }
+
+    /// 进入或退出降级模式, 发生变化时重建菜单
+    pub fn set_degraded(&mut self, config: &Config, degraded: bool) -> Result<()> {
+        if degraded == self.degraded {
+            return Ok(());
+        }
+        self.degraded = degraded;
+        self.backend.clear()?;
+        self.build(config)
+    }
+
+    /// 更新只读的状态信息, 只修改发生变化的项
+    pub fn update_status(&mut self, status: &TrayStatus) -> Result<()> {
+        let menu = &mut self.backend;
+        if status.pid != self.status.pid {
+            menu.set_label(self.game_label, &Self::game_text(status))?;
+        }
+        if status.server != self.status.server {
+            menu.set_label(self.server_label, &Self::server_text(status))?;
+        }
+        if status.disconnects != self.status.disconnects {
+            menu.set_label(self.count_label, &Self::count_text(status))?;
+        }
+        self.status = status.clone();
+        Ok(())
+    }
+
+    /// 切换托盘图标, 与当前图标相同时不做任何事
+    pub fn set_icon(&mut self, icon: TrayIcon) -> Result<()> {
+        if icon != self.icon {
+            self.backend.set_icon(icon)?;
+            self.icon = icon;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// 记录每次调用的托盘实现
+    #[derive(Default)]
+    struct RecordingBackend {
+        next_id: u32,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RecordingBackend {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    impl TrayBackend for RecordingBackend {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn add_label(&mut self, text: &str) -> Result<u32> {
+            self.next_id += 1;
+            self.record(format!("add_label {} {}", self.next_id, text));
+            Ok(self.next_id)
+        }
+
+        fn set_label(&mut self, id: u32, text: &str) -> Result<()> {
+            self.record(format!("set_label {} {}", id, text));
+            Ok(())
+        }
+
+        fn add_item(&mut self, text: &str, _callback: Callback) -> Result<u32> {
+            self.next_id += 1;
+            self.record(format!("add_item {} {}", self.next_id, text));
+            Ok(self.next_id)
+        }
+
+        fn set_item_label(&mut self, id: u32, text: &str) -> Result<()> {
+            self.record(format!("set_item_label {} {}", id, text));
+            Ok(())
+        }
+
+        fn add_separator(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn begin_submenu(&mut self, title: &str) -> Result<()> {
+            self.record(format!("begin_submenu {}", title));
+            Ok(())
+        }
+
+        fn end_submenu(&mut self) -> Result<()> {
+            self.record("end_submenu".to_string());
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<()> {
+            self.record("clear".to_string());
+            Ok(())
+        }
+
+        fn set_icon(&mut self, icon: TrayIcon) -> Result<()> {
+            self.record(format!("set_icon {:?}", icon));
+            Ok(())
+        }
+    }
+
+    /// 用默认配置创建托盘, 返回托盘和清空了初始菜单的调用记录
+    fn tray(config: &Config) -> (Tray, Arc<Mutex<Vec<String>>>) {
+        let backend = RecordingBackend::default();
+        let calls = Arc::clone(&backend.calls);
+        let (tx, _rx) = flume::unbounded();
+        let tray = Tray::new(Box::new(backend), tx, config, &TrayStatus::default(), false).unwrap();
+        calls.lock().unwrap().clear();
+        (tray, calls)
+    }
+
+    fn take(calls: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        std::mem::take(&mut *calls.lock().unwrap())
+    }
+
+    #[test]
+    fn update_status_only_touches_changed_labels() {
+        let (mut tray, calls) = tray(&Config::default());
+        let status = TrayStatus {
+            pid: Some(42),
+            ..TrayStatus::default()
+        };
+        tray.update_status(&status).unwrap();
+        assert_eq!(take(&calls), ["set_label 1 炉石: 已检测到 (PID 42)"]);
+
+        tray.update_status(&status).unwrap();
+        assert!(take(&calls).is_empty());
+
+        let status = TrayStatus {
+            disconnects: 2,
+            ..status
+        };
+        tray.update_status(&status).unwrap();
+        assert_eq!(take(&calls), ["set_label 3 本局拔线: 2 次"]);
+    }
+
+    #[test]
+    fn update_config_relabels_items_in_place() {
+        let mut config = Config::default();
+        let (mut tray, calls) = tray(&config);
+        config.exit_with_game = true;
+        tray.update_config(&config).unwrap();
+
+        let calls = take(&calls);
+        assert!(calls.iter().all(|call| call.starts_with("set_item_label")));
+        assert!(calls.iter().any(|call| call.ends_with("✓ 炉石退出时自动退出")));
+    }
+
+    #[test]
+    fn update_config_rebuilds_when_profiles_change() {
+        let mut config = Config::default();
+        let (mut tray, calls) = tray(&config);
+        config.profiles.insert("ranked".to_string(), Default::default());
+        tray.update_config(&config).unwrap();
+
+        let calls = take(&calls);
+        assert_eq!(calls.first().map(String::as_str), Some("clear"));
+        assert!(calls.iter().any(|call| call.starts_with("begin_submenu 配置档")));
+        assert!(calls.iter().any(|call| call.ends_with(" ranked")));
+    }
+
+    #[test]
+    fn set_degraded_rebuilds_with_restart_item() {
+        let config = Config::default();
+        let (mut tray, calls) = tray(&config);
+        tray.set_degraded(&config, false).unwrap();
+        assert!(take(&calls).is_empty());
+
+        tray.set_degraded(&config, true).unwrap();
+        let calls = take(&calls);
+        assert_eq!(calls.first().map(String::as_str), Some("clear"));
+        assert!(calls.iter().any(|call| call.ends_with(" 以管理员身份重新启动")));
+        assert!(calls.iter().any(|call| call.contains("开始拔线") && call.ends_with(" (不可用)")));
+    }
 }
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use super::{Callback, TrayBackend, TrayIcon};
+
+/// 不显示任何内容的托盘, 用于没有图形会话的环境
+#[derive(Default)]
+pub struct NoopBackend {
+    next_id: u32,
+}
+
+impl NoopBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TrayBackend for NoopBackend {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn add_label(&mut self, _text: &str) -> Result<u32> {
+        self.next_id += 1;
+        Ok(self.next_id)
+    }
+
+    fn set_label(&mut self, _id: u32, _text: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_item(&mut self, _text: &str, _callback: Callback) -> Result<u32> {
+        self.next_id += 1;
+        Ok(self.next_id)
+    }
+
+    fn set_item_label(&mut self, _id: u32, _text: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_separator(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn begin_submenu(&mut self, _title: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn end_submenu(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_icon(&mut self, _icon: TrayIcon) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::Result;
+use flume::{Selector, Sender, bounded};
+use ksni::menu::{StandardItem, SubMenu};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::{Callback, TrayBackend, TrayIcon};
+
+/// 各状态的图标, 除默认图标外都是内嵌 PNG 的 ICO 文件
+const ICONS: [(TrayIcon, &str, &[u8]); 5] = [
+    (TrayIcon::Idle, "hsarec.png", include_bytes!("../assets/hsarec.png")),
+    (TrayIcon::NoGame, "nogame.ico", include_bytes!("../assets/nogame.ico")),
+    (TrayIcon::Disconnecting, "stop.ico", include_bytes!("../assets/stop.ico")),
+    (TrayIcon::Waiting, "waiting.ico", include_bytes!("../assets/waiting.ico")),
+    (TrayIcon::Error, "error.ico", include_bytes!("../assets/error.ico")),
+];
+
+enum Entry {
+    Label(u32, String),
+    Item(u32, String, Callback),
+    Separator,
+    SubMenu(String, Vec<Entry>),
+}
+
+impl Entry {
+    /// 修改 id 对应的菜单项文字, 包括子菜单中的菜单项, 找到时返回 true
+    fn set_label(entries: &mut [Entry], id: u32, text: &str) -> bool {
+        entries.iter_mut().any(|entry| match entry {
+            Entry::Label(entry_id, label) | Entry::Item(entry_id, label, _) if *entry_id == id => {
+                *label = text.to_string();
+                true
+            }
+            Entry::SubMenu(_, children) => Entry::set_label(children, id, text),
+            _ => false,
+        })
+    }
+
+    fn menu_item(&self) -> ksni::MenuItem<SniTray> {
+        match self {
+            Entry::Label(_, text) => StandardItem {
+                label: text.clone(),
+                enabled: false,
+                ..Default::default()
+            }
+            .into(),
+            Entry::Item(_, text, callback) => {
+                let callback = callback.clone();
+                StandardItem {
+                    label: text.clone(),
+                    activate: Box::new(move |_: &mut SniTray| callback()),
+                    ..Default::default()
+                }
+                .into()
+            }
+            Entry::Separator => ksni::MenuItem::Separator,
+            Entry::SubMenu(title, children) => SubMenu {
+                label: title.clone(),
+                submenu: children.iter().map(Entry::menu_item).collect(),
+                ..Default::default()
+            }
+            .into(),
+        }
+    }
+}
+
+/// 等待托盘注册到 StatusNotifierWatcher 的最长时间
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 通过 D-Bus 发布的 StatusNotifierItem 托盘内容
+struct SniTray {
+    entries: Vec<Entry>,
+    icon: TrayIcon,
+    icons: HashMap<TrayIcon, Vec<ksni::Icon>>,
+    /// 注册成功时通知 `SniBackend::new`
+    online_tx: Sender<()>,
+    /// 是否注册成功过
+    registered: AtomicBool,
+}
+
+impl ksni::Tray for SniTray {
+    fn id(&self) -> String {
+        "hsarec".to_string()
+    }
+
+    fn title(&self) -> String {
+        "Hsarec".to_string()
+    }
+
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.icons.get(&self.icon).cloned().unwrap_or_default()
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        self.entries.iter().map(Entry::menu_item).collect()
+    }
+
+    fn watcher_online(&self) {
+        self.registered.store(true, Ordering::SeqCst);
+        let _ = self.online_tx.try_send(());
+    }
+
+    /// 启动时没有 StatusNotifierWatcher 则停止服务, 以便改用其他托盘实现;
+    /// 注册成功之后 watcher 消失(例如面板重启)时继续运行, 等它重新出现后再注册
+    fn watcher_offine(&self) -> bool {
+        self.registered.load(Ordering::SeqCst)
+    }
+}
+
+/// Linux 下的 StatusNotifierItem/AppIndicator 托盘, 图标来自 `src/assets` 中的 PNG/ICO 文件
+pub struct SniBackend {
+    handle: ksni::Handle<SniTray>,
+    next_id: u32,
+    /// 正在添加子菜单中的菜单项
+    in_submenu: bool,
+}
+
+impl SniBackend {
+    /// 在单独的线程中运行托盘服务, 等到注册成功后返回, 没有 D-Bus 会话或 StatusNotifierWatcher 时返回错误
+    pub fn new() -> Result<Self> {
+        let (online_tx, online_rx) = bounded(1);
+        let service = ksni::TrayService::new(SniTray {
+            entries: Vec::new(),
+            icon: TrayIcon::Idle,
+            icons: ICONS
+                .iter()
+                .map(|(icon, name, data)| (*icon, load_icon(name, data)))
+                .collect(),
+            online_tx,
+            registered: AtomicBool::new(false),
+        });
+        let handle = service.handle();
+        let (stopped_tx, stopped_rx) = bounded(1);
+        std::thread::Builder::new()
+            .name("sni-tray".to_string())
+            .spawn(move || {
+                let reason = match service.run() {
+                    Ok(()) => "没有可用的 StatusNotifierWatcher".to_string(),
+                    Err(e) => format!("D-Bus 错误: {}", e),
+                };
+                if stopped_tx.send(reason.clone()).is_err() {
+                    warn!("警告: 托盘服务已停止: {}", reason);
+                }
+            })?;
+        let started = Selector::new()
+            .recv(&online_rx, |_| Ok(()))
+            .recv(&stopped_rx, |reason| {
+                Err(anyhow::anyhow!("{}", reason.unwrap_or_default()))
+            })
+            .wait_timeout(STARTUP_TIMEOUT)
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("等待注册托盘超时")));
+        if let Err(e) = started {
+            handle.shutdown();
+            return Err(e);
+        }
+        Ok(SniBackend {
+            handle,
+            next_id: 0,
+            in_submenu: false,
+        })
+    }
+
+    fn push(&mut self, entry: impl FnOnce(u32) -> Entry) -> u32 {
+        self.next_id += 1;
+        self.push_entry(entry(self.next_id));
+        self.next_id
+    }
+
+    /// 添加到主菜单, 或正在添加的子菜单中
+    fn push_entry(&mut self, entry: Entry) {
+        let in_submenu = self.in_submenu;
+        self.handle.update(move |tray| match tray.entries.last_mut() {
+            Some(Entry::SubMenu(_, children)) if in_submenu => children.push(entry),
+            _ => tray.entries.push(entry),
+        });
+    }
+
+    fn set(&mut self, id: u32, text: &str) {
+        let text = text.to_string();
+        self.handle.update(move |tray| {
+            Entry::set_label(&mut tray.entries, id, &text);
+        });
+    }
+}
+
+impl TrayBackend for SniBackend {
+    fn name(&self) -> &'static str {
+        "sni"
+    }
+
+    fn add_label(&mut self, text: &str) -> Result<u32> {
+        Ok(self.push(|id| Entry::Label(id, text.to_string())))
+    }
+
+    fn set_label(&mut self, id: u32, text: &str) -> Result<()> {
+        self.set(id, text);
+        Ok(())
+    }
+
+    fn add_item(&mut self, text: &str, callback: Callback) -> Result<u32> {
+        Ok(self.push(|id| Entry::Item(id, text.to_string(), callback)))
+    }
+
+    fn set_item_label(&mut self, id: u32, text: &str) -> Result<()> {
+        self.set(id, text);
+        Ok(())
+    }
+
+    fn add_separator(&mut self) -> Result<()> {
+        self.push_entry(Entry::Separator);
+        Ok(())
+    }
+
+    fn begin_submenu(&mut self, title: &str) -> Result<()> {
+        anyhow::ensure!(!self.in_submenu, "不支持嵌套子菜单");
+        self.push_entry(Entry::SubMenu(title.to_string(), Vec::new()));
+        self.in_submenu = true;
+        Ok(())
+    }
+
+    fn end_submenu(&mut self) -> Result<()> {
+        self.in_submenu = false;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.handle.update(|tray| tray.entries.clear());
+        self.in_submenu = false;
+        Ok(())
+    }
+
+    fn set_icon(&mut self, icon: TrayIcon) -> Result<()> {
+        self.handle.update(move |tray| tray.icon = icon);
+        Ok(())
+    }
+}
+
+/// 解码 PNG 或内嵌 PNG 的 ICO 图标, 失败时只记录警告, 托盘显示为空白图标
+fn load_icon(name: &str, data: &[u8]) -> Vec<ksni::Icon> {
+    let png = if name.ends_with(".ico") {
+        ico_png(data)
+    } else {
+        Ok(data)
+    };
+    match png.and_then(decode_png) {
+        Ok(icon) => vec![icon],
+        Err(e) => {
+            warn!("警告: 无法加载托盘图标 {}: {}", name, e);
+            Vec::new()
+        }
+    }
+}
+
+/// 取出 ICO 文件中尺寸最大的 PNG 图像
+fn ico_png(ico: &[u8]) -> Result<&[u8]> {
+    let count = match ico {
+        [0, 0, 1, 0, lo, hi, ..] => u16::from_le_bytes([*lo, *hi]) as usize,
+        _ => anyhow::bail!("不是 ICO 文件"),
+    };
+    (0..count)
+        .filter_map(|i| {
+            let entry = ico.get(6 + i * 16..6 + (i + 1) * 16)?;
+            // 宽度为 0 表示 256
+            let width = if entry[0] == 0 { 256 } else { entry[0] as u32 };
+            let size = u32::from_le_bytes(entry[8..12].try_into().ok()?) as usize;
+            let offset = u32::from_le_bytes(entry[12..16].try_into().ok()?) as usize;
+            let data = ico.get(offset..offset.checked_add(size)?)?;
+            data.starts_with(b"\x89PNG").then_some((width, data))
+        })
+        .max_by_key(|(width, _)| *width)
+        .map(|(_, data)| data)
+        .ok_or_else(|| anyhow::anyhow!("ICO 文件中没有 PNG 图像"))
+}
+
+/// 将 PNG 解码为 StatusNotifierItem 使用的 ARGB32(网络字节序)像素
+fn decode_png(png: &[u8]) -> Result<ksni::Icon> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let pixels = &buf[..info.buffer_size()];
+    let data = match info.color_type {
+        png::ColorType::Rgba => pixels
+            .chunks_exact(4)
+            .flat_map(|p| [p[3], p[0], p[1], p[2]])
+            .collect(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [255, p[0], p[1], p[2]])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[1], p[0], p[0], p[0]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [255, g, g, g]).collect(),
+        png::ColorType::Indexed => anyhow::bail!("未展开的调色板图像"),
+    };
+    Ok(ksni::Icon {
+        width: info.width as i32,
+        height: info.height as i32,
+        data,
+    })
+}
@@ -0,0 +1,444 @@
+use anyhow::Result;
+use log::error;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicIsize, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Shell::{
+    NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+    Shell_NotifyIconW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DispatchMessageW,
+    GetCursorPos, GetMessageW, GetSystemMetrics, HICON, HMENU, IMAGE_ICON, LR_SHARED, LoadImageW,
+    MENU_ITEM_FLAGS, MENUITEMINFOW, MF_GRAYED, MF_POPUP, MF_SEPARATOR, MF_STRING, MIIM_STRING, MSG,
+    PostMessageW, PostQuitMessage, RegisterClassW, RegisterWindowMessageW, SM_CXSMICON,
+    SM_CYSMICON, SetForegroundWindow, SetMenuItemInfoW, TPM_BOTTOMALIGN, TPM_RIGHTBUTTON,
+    TrackPopupMenu, TranslateMessage, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP, WM_CLOSE, WM_COMMAND,
+    WM_DESTROY, WM_LBUTTONUP, WM_NULL, WM_RBUTTONUP, WNDCLASSW,
+};
+use windows::core::{HSTRING, PCWSTR, PWSTR, w};
+
+use super::{Callback, TrayBackend, TrayIcon};
+
+/// 托盘图标通知窗口的消息
+const TRAY_MESSAGE: u32 = WM_APP + 1;
+/// 请求窗口线程在菜单关闭后销毁旧菜单, `lParam` 为菜单句柄
+const RETIRE_MENU_MESSAGE: u32 = WM_APP + 2;
+const TOOLTIP: &str = "Hsarec";
+
+/// 窗口线程和托盘之间共享的状态, 句柄以整数保存以便跨线程传递
+struct Shared {
+    /// 弹出的主菜单
+    menu: AtomicIsize,
+    icon: AtomicIsize,
+    /// 资源管理器重启时广播的消息, 收到后重新添加图标
+    taskbar_created: AtomicU32,
+    callbacks: Mutex<HashMap<u32, Callback>>,
+}
+
+thread_local! {
+    /// 窗口过程所在线程的共享状态
+    static SHARED: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+    /// 菜单是否正在显示, 显示期间不销毁旧菜单
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    /// 等待菜单关闭后销毁的旧菜单
+    static RETIRED: RefCell<Vec<isize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 基于 Win32 通知区域 API 的 Windows 托盘, 图标来自 `manifest.rc` 中嵌入的资源
+///
+/// 在单独的线程中创建隐藏窗口接收托盘图标和菜单的消息, 子菜单为原生的弹出菜单
+pub struct Win32Backend {
+    hwnd: isize,
+    shared: Arc<Shared>,
+    /// 正在添加的子菜单, 没有时添加到主菜单
+    submenu: Option<isize>,
+    /// 菜单项 id 到所在菜单的映射
+    items: HashMap<u32, isize>,
+    next_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Win32Backend {
+    /// 在单独的线程中创建窗口和托盘图标, 等到图标添加成功后返回
+    pub fn new() -> Result<Self> {
+        let shared = Arc::new(Shared {
+            menu: AtomicIsize::new(unsafe { CreatePopupMenu() }?.0 as isize),
+            icon: AtomicIsize::new(load_icon(TrayIcon::Idle)?.0 as isize),
+            taskbar_created: AtomicU32::new(0),
+            callbacks: Mutex::new(HashMap::new()),
+        });
+        let (hwnd_tx, hwnd_rx) = std::sync::mpsc::channel();
+        let thread_shared = Arc::clone(&shared);
+        let thread = std::thread::spawn(move || {
+            SHARED.with(|shared| *shared.borrow_mut() = Some(thread_shared));
+            match create_window() {
+                Ok(hwnd) => {
+                    let _ = hwnd_tx.send(Ok(hwnd.0 as isize));
+                    run_loop();
+                }
+                Err(e) => {
+                    let _ = hwnd_tx.send(Err(e));
+                }
+            }
+        });
+        let hwnd = hwnd_rx.recv()??;
+        Ok(Win32Backend {
+            hwnd,
+            shared,
+            submenu: None,
+            items: HashMap::new(),
+            next_id: 0,
+            thread: Some(thread),
+        })
+    }
+
+    /// 当前正在添加菜单项的菜单
+    fn current_menu(&self) -> isize {
+        self.submenu
+            .unwrap_or_else(|| self.shared.menu.load(Ordering::SeqCst))
+    }
+
+    /// 分配新的菜单项 id, `WM_COMMAND` 中只有 16 位, 0 保留不用
+    fn allocate_id(&mut self) -> u32 {
+        self.next_id = self.next_id % u32::from(u16::MAX) + 1;
+        self.next_id
+    }
+
+    fn append(&mut self, flags: MENU_ITEM_FLAGS, text: &str) -> Result<u32> {
+        let id = self.allocate_id();
+        let menu = self.current_menu();
+        unsafe { AppendMenuW(menu_handle(menu), flags, id as usize, &HSTRING::from(text)) }?;
+        self.items.insert(id, menu);
+        Ok(id)
+    }
+
+    fn set_text(&mut self, id: u32, text: &str) -> Result<()> {
+        let menu = *self
+            .items
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("菜单项 {} 不存在", id))?;
+        let mut text: Vec<u16> = text.encode_utf16().chain(Some(0)).collect();
+        let info = MENUITEMINFOW {
+            cbSize: std::mem::size_of::<MENUITEMINFOW>() as u32,
+            fMask: MIIM_STRING,
+            dwTypeData: PWSTR(text.as_mut_ptr()),
+            ..Default::default()
+        };
+        unsafe { SetMenuItemInfoW(menu_handle(menu), id, false, &info) }?;
+        Ok(())
+    }
+}
+
+impl TrayBackend for Win32Backend {
+    fn name(&self) -> &'static str {
+        "win32"
+    }
+
+    fn add_label(&mut self, text: &str) -> Result<u32> {
+        self.append(MF_STRING | MF_GRAYED, text)
+    }
+
+    fn set_label(&mut self, id: u32, text: &str) -> Result<()> {
+        self.set_text(id, text)
+    }
+
+    fn add_item(&mut self, text: &str, callback: Callback) -> Result<u32> {
+        let id = self.append(MF_STRING, text)?;
+        self.shared
+            .callbacks
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取托盘回调锁: {}", e))?
+            .insert(id, callback);
+        Ok(id)
+    }
+
+    fn set_item_label(&mut self, id: u32, text: &str) -> Result<()> {
+        self.set_text(id, text)
+    }
+
+    fn add_separator(&mut self) -> Result<()> {
+        let menu = self.current_menu();
+        unsafe { AppendMenuW(menu_handle(menu), MF_SEPARATOR, 0, PCWSTR::null()) }?;
+        Ok(())
+    }
+
+    fn begin_submenu(&mut self, title: &str) -> Result<()> {
+        anyhow::ensure!(self.submenu.is_none(), "不支持嵌套子菜单");
+        let submenu = unsafe { CreatePopupMenu() }?;
+        let menu = self.current_menu();
+        unsafe {
+            AppendMenuW(
+                menu_handle(menu),
+                MF_STRING | MF_POPUP,
+                submenu.0 as usize,
+                &HSTRING::from(title),
+            )
+        }?;
+        self.submenu = Some(submenu.0 as isize);
+        Ok(())
+    }
+
+    fn end_submenu(&mut self) -> Result<()> {
+        self.submenu = None;
+        Ok(())
+    }
+
+    /// 换上新的空菜单, 旧菜单交给窗口线程在菜单关闭后销毁
+    fn clear(&mut self) -> Result<()> {
+        let menu = unsafe { CreatePopupMenu() }?;
+        let old = self.shared.menu.swap(menu.0 as isize, Ordering::SeqCst);
+        self.submenu = None;
+        self.items.clear();
+        self.shared
+            .callbacks
+            .lock()
+            .map_err(|e| anyhow::anyhow!("无法获取托盘回调锁: {}", e))?
+            .clear();
+        unsafe {
+            PostMessageW(
+                Some(window_handle(self.hwnd)),
+                RETIRE_MENU_MESSAGE,
+                WPARAM(0),
+                LPARAM(old),
+            )
+        }?;
+        Ok(())
+    }
+
+    fn set_icon(&mut self, icon: TrayIcon) -> Result<()> {
+        let icon = load_icon(icon)?;
+        self.shared.icon.store(icon.0 as isize, Ordering::SeqCst);
+        let data = NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: window_handle(self.hwnd),
+            uID: 1,
+            uFlags: NIF_ICON,
+            hIcon: icon,
+            ..Default::default()
+        };
+        anyhow::ensure!(
+            unsafe { Shell_NotifyIconW(NIM_MODIFY, &data) }.as_bool(),
+            "无法修改托盘图标"
+        );
+        Ok(())
+    }
+}
+
+impl Drop for Win32Backend {
+    fn drop(&mut self) {
+        let _ = unsafe {
+            PostMessageW(
+                Some(window_handle(self.hwnd)),
+                WM_CLOSE,
+                WPARAM(0),
+                LPARAM(0),
+            )
+        };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn menu_handle(menu: isize) -> HMENU {
+    HMENU(menu as *mut _)
+}
+
+fn window_handle(hwnd: isize) -> HWND {
+    HWND(hwnd as *mut _)
+}
+
+/// 从嵌入的资源加载托盘尺寸的图标, 资源编号见 `manifest.rc`
+fn load_icon(icon: TrayIcon) -> Result<HICON> {
+    let resource: usize = match icon {
+        TrayIcon::Idle => 1,
+        TrayIcon::Disconnecting => 3,
+        TrayIcon::NoGame => 4,
+        TrayIcon::Waiting => 5,
+        TrayIcon::Error => 6,
+    };
+    let handle = unsafe {
+        LoadImageW(
+            Some(GetModuleHandleW(None)?.into()),
+            PCWSTR(resource as *const u16),
+            IMAGE_ICON,
+            GetSystemMetrics(SM_CXSMICON),
+            GetSystemMetrics(SM_CYSMICON),
+            LR_SHARED,
+        )
+    }
+    .map_err(|e| anyhow::anyhow!("无法加载托盘图标 {:?}: {}", icon, e))?;
+    Ok(HICON(handle.0))
+}
+
+/// 在当前线程中创建接收托盘消息的隐藏窗口, 并添加托盘图标
+fn create_window() -> Result<HWND> {
+    let module = unsafe { GetModuleHandleW(None) }?;
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        hInstance: module.into(),
+        lpszClassName: w!("hsarec_tray"),
+        ..Default::default()
+    };
+    unsafe { RegisterClassW(&class) };
+    with_shared(|shared| {
+        let taskbar_created = unsafe { RegisterWindowMessageW(w!("TaskbarCreated")) };
+        shared
+            .taskbar_created
+            .store(taskbar_created, Ordering::SeqCst);
+    });
+    let hwnd = unsafe {
+        CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            w!("hsarec_tray"),
+            w!("Hsarec"),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(module.into()),
+            None,
+        )
+    }?;
+    anyhow::ensure!(add_icon(hwnd), "无法添加托盘图标");
+    Ok(hwnd)
+}
+
+/// 添加托盘图标, 资源管理器重启后也要重新添加
+fn add_icon(hwnd: HWND) -> bool {
+    with_shared(|shared| {
+        let mut data = NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: hwnd,
+            uID: 1,
+            uFlags: NIF_MESSAGE | NIF_ICON | NIF_TIP,
+            uCallbackMessage: TRAY_MESSAGE,
+            hIcon: HICON(shared.icon.load(Ordering::SeqCst) as *mut _),
+            ..Default::default()
+        };
+        for (dst, src) in data.szTip.iter_mut().zip(TOOLTIP.encode_utf16()) {
+            *dst = src;
+        }
+        unsafe { Shell_NotifyIconW(NIM_ADD, &data) }.as_bool()
+    })
+    .unwrap_or(false)
+}
+
+fn with_shared<T>(f: impl FnOnce(&Shared) -> T) -> Option<T> {
+    SHARED.with(|shared| shared.borrow().as_deref().map(f))
+}
+
+fn run_loop() {
+    let mut msg = MSG::default();
+    while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
+        unsafe {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+/// 在鼠标位置弹出主菜单, 菜单关闭后销毁期间换下的旧菜单
+fn show_menu(hwnd: HWND) {
+    let Some(menu) = with_shared(|shared| shared.menu.load(Ordering::SeqCst)) else {
+        return;
+    };
+    let mut point = POINT::default();
+    if unsafe { GetCursorPos(&mut point) }.is_err() {
+        return;
+    }
+    TRACKING.set(true);
+    unsafe {
+        // 先把窗口切到前台, 否则点击菜单外部时菜单不会关闭
+        let _ = SetForegroundWindow(hwnd);
+        let _ = TrackPopupMenu(
+            menu_handle(menu),
+            TPM_RIGHTBUTTON | TPM_BOTTOMALIGN,
+            point.x,
+            point.y,
+            None,
+            hwnd,
+            None,
+        );
+        let _ = PostMessageW(Some(hwnd), WM_NULL, WPARAM(0), LPARAM(0));
+    }
+    TRACKING.set(false);
+    destroy_retired_menus();
+}
+
+fn destroy_retired_menus() {
+    if TRACKING.get() {
+        return;
+    }
+    for menu in RETIRED.take() {
+        if let Err(e) = unsafe { DestroyMenu(menu_handle(menu)) } {
+            error!("无法销毁托盘菜单: {}", e);
+        }
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        TRAY_MESSAGE => {
+            if matches!(lparam.0 as u32, WM_LBUTTONUP | WM_RBUTTONUP) {
+                show_menu(hwnd);
+            }
+            LRESULT(0)
+        }
+        WM_COMMAND => {
+            let id = (wparam.0 & 0xffff) as u32;
+            let callback = with_shared(|shared| {
+                shared
+                    .callbacks
+                    .lock()
+                    .ok()
+                    .and_then(|callbacks| callbacks.get(&id).cloned())
+            })
+            .flatten();
+            if let Some(callback) = callback {
+                callback();
+            }
+            LRESULT(0)
+        }
+        RETIRE_MENU_MESSAGE => {
+            RETIRED.with_borrow_mut(|retired| retired.push(lparam.0));
+            destroy_retired_menus();
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            let data = NOTIFYICONDATAW {
+                cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+                hWnd: hwnd,
+                uID: 1,
+                ..Default::default()
+            };
+            unsafe {
+                let _ = Shell_NotifyIconW(NIM_DELETE, &data);
+                PostQuitMessage(0);
+            }
+            LRESULT(0)
+        }
+        msg if msg != 0
+            && with_shared(|shared| shared.taskbar_created.load(Ordering::SeqCst)) == Some(msg) =>
+        {
+            if !add_icon(hwnd) {
+                error!("资源管理器重启后无法重新添加托盘图标");
+            }
+            LRESULT(0)
+        }
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}